@@ -17,7 +17,7 @@ mod tests {
     fn bench_init_wormomancer(b: &mut Bencher) {
         b.iter(|| {
             let png_path = "/pictures/wormomancer.png";
-            let png = PNG::open(png_path);
+            let png = PNG::open(png_path).unwrap();
         });
     }
 
@@ -25,8 +25,8 @@ mod tests {
     fn bench_load_data_wormomancer(b: &mut Bencher) {
         b.iter(|| {
             let png_path = "/pictures/wormomancer.png";
-            let png = PNG::open(png_path);
-            let data = png.get_image_data();
+            let png = PNG::open(png_path).unwrap();
+            let data = png.get_image_data().unwrap();
         });
     }
 
@@ -34,7 +34,7 @@ mod tests {
     fn bench_init_covfefe(b: &mut Bencher) {
         b.iter(|| {
             let png_path = "/pictures/covfefe.png";
-            let png = PNG::open(png_path);
+            let png = PNG::open(png_path).unwrap();
         });
     }
 
@@ -42,8 +42,8 @@ mod tests {
     fn bench_load_data_covfefe(b: &mut Bencher) {
         b.iter(|| {
             let png_path = "/pictures/covfefe.png";
-            let png = PNG::open(png_path);
-            let data = png.get_image_data();
+            let png = PNG::open(png_path).unwrap();
+            let data = png.get_image_data().unwrap();
         });
     }
 
@@ -51,8 +51,8 @@ mod tests {
     fn bench_load_data_big_white(b: &mut Bencher) {
         b.iter(|| {
             let png_path = "/pictures/big_white.png";
-            let png = PNG::open(png_path);
-            let data = png.get_image_data();
+            let png = PNG::open(png_path).unwrap();
+            let data = png.get_image_data().unwrap();
 
             assert_eq!(data.len(), 192000000);
         });
@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use crate::chunks::{acTL, BlendOp, ColorType, DisposeOp, fcTL, TryFromChunk, IHDR, PLTE, tRNS_Indexed};
+use crate::error::PngError;
+use crate::png::{DecodedImage, PixelFormat, PngRef, PNG};
+use crate::{transform, utils};
+
+/// A frame of an animation, composited onto the canvas of all frames before it.
+#[derive(Debug)]
+pub struct Frame {
+    /// The whole canvas as RGBA8, with the dimensions of the default image.
+    pub image: DecodedImage,
+    /// How long the frame is shown before the next one.
+    pub delay: Duration,
+}
+
+/// The control chunk of a frame and the chunks holding its image data.
+struct FrameData<'a> {
+    fctl: fcTL,
+    /// IDAT for the default image, fdAT for all other frames.
+    chunk_type: &'static str,
+    data: Vec<&'a [u8]>,
+}
+
+/// Decodes and composites the frames of an APNG in order, see `PNG::frames`.
+pub struct Frames<'a> {
+    ihdr: IHDR,
+    actl: Option<acTL>,
+    plte: Option<PLTE>,
+    trns: Option<tRNS_Indexed>,
+    color_key: Option<Vec<u16>>,
+    frames: std::vec::IntoIter<FrameData<'a>>,
+    /// Allocated once the first frame has decoded, so that the IHDR dimensions alone can't exhaust memory.
+    canvas: Vec<u8>,
+    first: bool,
+    finished: bool,
+}
+
+impl<'a> Frames<'a> {
+    pub(crate) fn new(png: &PngRef<'a>) -> Result<Frames<'a>, PngError> {
+        let ihdr = png.ihdr()?;
+        let actl = png.find_chunk("acTL")
+            .map(acTL::try_from_chunk_ref)
+            .transpose()?;
+
+        // Group image data by the fcTL preceding it. The default image is only a frame if it has an fcTL,
+        // or if there is no animation at all.
+        let mut frames = vec![];
+        if actl.is_none() {
+            let data = png.chunks.iter()
+                .filter(|c| c.chunk_type == "IDAT")
+                .map(|c| c.data)
+                .collect();
+            frames.push(FrameData { fctl: fcTL::for_default_image(&ihdr), chunk_type: "IDAT", data });
+        } else {
+            let mut current: Option<FrameData<'a>> = None;
+            let mut idat_seen = false;
+            // fcTL and fdAT share one sequence, which starts at 0 and has no gaps
+            let mut sequence_number = 0;
+            for chunk in &png.chunks {
+                if matches!(chunk.chunk_type, "fcTL" | "fdAT") {
+                    let found = chunk.data.get(..4)
+                        .map(utils::read_be_u32)
+                        .ok_or(PngError::invalid_chunk(chunk.chunk_type, "Missing sequence number"))?;
+                    if found != sequence_number {
+                        return Err(PngError::invalid_chunk(
+                            chunk.chunk_type,
+                            format!("Sequence number {} is out of order, expected {}", found, sequence_number),
+                        ));
+                    }
+                    sequence_number += 1;
+                }
+
+                match chunk.chunk_type {
+                    "fcTL" => {
+                        frames.extend(current.take());
+
+                        let fctl = fcTL::try_from_chunk_ref(chunk)?;
+                        if fctl.x_offset() as u64 + fctl.width() as u64 > ihdr.width as u64
+                            || fctl.y_offset() as u64 + fctl.height() as u64 > ihdr.height as u64 {
+                            return Err(PngError::invalid_chunk(chunk.chunk_type, "Frame does not fit on the canvas"));
+                        }
+                        // An fcTL before the image data makes the default image the first frame, which must cover
+                        // the whole canvas
+                        let chunk_type = if idat_seen { "fdAT" } else { "IDAT" };
+                        let covers_canvas = (fctl.width(), fctl.height(), fctl.x_offset(), fctl.y_offset())
+                            == (ihdr.width, ihdr.height, 0, 0);
+                        if chunk_type == "IDAT" && !covers_canvas {
+                            return Err(PngError::invalid_chunk(chunk.chunk_type, "Frame of the default image does not match IHDR"));
+                        }
+                        current = Some(FrameData { fctl, chunk_type, data: vec![] });
+                    }
+                    "IDAT" => {
+                        idat_seen = true;
+                        if let Some(frame) = &mut current {
+                            if frame.chunk_type != "IDAT" {
+                                return Err(PngError::invalid_chunk(chunk.chunk_type, "Image data after the first frame"));
+                            }
+                            frame.data.push(chunk.data);
+                        }
+                    }
+                    "fdAT" => {
+                        let frame = current.as_mut()
+                            .ok_or(PngError::invalid_chunk(chunk.chunk_type, "Frame data without a preceding fcTL"))?;
+                        if frame.chunk_type != "fdAT" {
+                            return Err(PngError::invalid_chunk(chunk.chunk_type, "Frame data in the frame of the default image"));
+                        }
+                        // Skip the sequence number
+                        frame.data.push(&chunk.data[4..]);
+                    }
+                    _ => {}
+                }
+            }
+            frames.extend(current);
+        }
+
+        let (plte, trns) = if ihdr.color_type == ColorType::IndexedColor {
+            let plte = png.find_chunk("PLTE")
+                .ok_or(PngError::MissingChunk(String::from("PLTE")))?;
+            let trns = png.find_chunk("tRNS")
+                .map(tRNS_Indexed::try_from_chunk_ref)
+                .transpose()?;
+
+            (Some(PLTE::try_from_chunk_ref(plte)?), trns)
+        } else {
+            (None, None)
+        };
+
+        Ok(Frames {
+            color_key: png.color_key(ihdr.color_type)?,
+            canvas: vec![],
+            ihdr,
+            actl,
+            plte,
+            trns,
+            frames: frames.into_iter(),
+            first: true,
+            finished: false,
+        })
+    }
+
+    /// The animation control chunk, None if the PNG is not animated.
+    pub fn actl(&self) -> Option<&acTL> {
+        self.actl.as_ref()
+    }
+
+    /// Decodes the frame's own image data into RGBA8.
+    fn decode_frame(&self, fctl: &fcTL, data: &[&[u8]]) -> Result<DecodedImage, PngError> {
+        let ihdr = self.ihdr.with_dimensions(fctl.width(), fctl.height());
+        let mut image = DecodedImage {
+            width: ihdr.width,
+            height: ihdr.height,
+            color_type: ihdr.color_type,
+            bit_depth: ihdr.bit_depth,
+            data: PNG::decode_image_data(&data.concat(), &ihdr)?,
+        };
+
+        if let Some(plte) = &self.plte {
+            image = transform::expand_palette(image, plte, self.trns.as_ref())?;
+        }
+
+        Ok(transform::convert(image, self.color_key.as_deref(), PixelFormat::Rgba8))
+    }
+
+    fn render(&mut self, frame_data: &FrameData) -> Result<Frame, PngError> {
+        if frame_data.data.is_empty() {
+            return Err(PngError::MissingChunk(String::from(frame_data.chunk_type)));
+        }
+        let fctl = &frame_data.fctl;
+        let image = self.decode_frame(fctl, &frame_data.data)?;
+
+        if self.first {
+            let canvas_length = self.ihdr.image_length(self.ihdr.width, self.ihdr.height, 4)?;
+            self.canvas.try_reserve_exact(canvas_length).map_err(|_| self.ihdr.too_large())?;
+            self.canvas.resize(canvas_length, 0);
+        }
+
+        // Previous on the first frame is treated as Background
+        let dispose_op = match fctl.dispose_op() {
+            DisposeOp::Previous if self.first => { DisposeOp::Background }
+            dispose_op => { dispose_op }
+        };
+        let previous = if dispose_op == DisposeOp::Previous { Some(self.canvas.clone()) } else { None };
+
+        // Blend the frame into its region of the canvas
+        let canvas_width = self.ihdr.width as usize;
+        let frame_width = fctl.width() as usize;
+        for y in 0..fctl.height() as usize {
+            let canvas_row = ((y + fctl.y_offset() as usize) * canvas_width + fctl.x_offset() as usize) * 4;
+            let canvas_row = &mut self.canvas[canvas_row..canvas_row + frame_width * 4];
+            let frame_row = &image.data[y * frame_width * 4..(y + 1) * frame_width * 4];
+
+            match fctl.blend_op() {
+                BlendOp::Source => { canvas_row.copy_from_slice(frame_row) }
+                BlendOp::Over => {
+                    for (dst, src) in canvas_row.chunks_exact_mut(4).zip(frame_row.chunks_exact(4)) {
+                        blend_over(dst, src);
+                    }
+                }
+            }
+        }
+
+        let frame = Frame {
+            image: DecodedImage {
+                width: self.ihdr.width,
+                height: self.ihdr.height,
+                color_type: ColorType::TrueColorAlpha,
+                bit_depth: 8,
+                data: self.canvas.clone(),
+            },
+            delay: fctl.delay(),
+        };
+
+        // Prepare the canvas for the next frame
+        match (dispose_op, previous) {
+            (DisposeOp::Background, _) => {
+                for y in 0..fctl.height() as usize {
+                    let canvas_row = ((y + fctl.y_offset() as usize) * canvas_width + fctl.x_offset() as usize) * 4;
+                    self.canvas[canvas_row..canvas_row + frame_width * 4].fill(0);
+                }
+            }
+            (DisposeOp::Previous, Some(previous)) => { self.canvas = previous }
+            _ => {}
+        }
+        self.first = false;
+
+        Ok(frame)
+    }
+}
+
+/// Alpha composites an RGBA8 pixel over another, https://wiki.mozilla.org/APNG_Specification#.60fcTL.60:_The_Frame_Control_Chunk
+fn blend_over(dst: &mut [u8], src: &[u8]) {
+    let src_alpha = src[3] as u32;
+    let dst_alpha = dst[3] as u32;
+    match src_alpha {
+        0 => {}
+        255 => { dst.copy_from_slice(src) }
+        _ => {
+            // Weight of the destination after the source covers it
+            let dst_weight = dst_alpha * (255 - src_alpha) / 255;
+            let alpha = src_alpha + dst_weight;
+            for i in 0..3 {
+                dst[i] = ((src[i] as u32 * src_alpha + dst[i] as u32 * dst_weight + alpha / 2) / alpha) as u8;
+            }
+            dst[3] = alpha as u8;
+        }
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let frame_data = self.frames.next()?;
+        let frame = self.render(&frame_data);
+        self.finished = frame.is_err();
+        Some(frame)
+    }
+}
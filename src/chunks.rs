@@ -1,43 +1,213 @@
+use std::cmp::max;
 use std::fmt::{Display, Formatter};
-use std::io::Read;
-
-use compress::zlib;
+use std::time::Duration;
 
+use crate::error::PngError;
+use crate::exif::{self, ExifEntry, IfdKind};
+use crate::interlace;
 use crate::u8_enum;
-use crate::png::Chunk;
-use crate::utils::{read_be_u16, read_be_u32, read_until_null};
+use crate::png::{Chunk, ChunkRef};
+use crate::utils::{inflate, read_be_u16, read_be_u32, read_until_null};
 
 pub trait FromChunk {
     fn from_chunk(chunk: &Chunk) -> Self;
 }
 
-#[derive(Debug)]
+pub trait TryFromChunk: Sized {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError>;
+
+    fn try_from_chunk(chunk: &Chunk) -> Result<Self, PngError> {
+        Self::try_from_chunk_ref(&chunk.as_chunk_ref())
+    }
+}
+
+/// Panics if the chunk is invalid, use `TryFromChunk` for untrusted input.
+impl<T: TryFromChunk> FromChunk for T {
+    fn from_chunk(chunk: &Chunk) -> Self {
+        T::try_from_chunk(chunk).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Checks that a chunk holds at least `length` bytes of data.
+fn check_length(chunk: &ChunkRef, length: usize) -> Result<(), PngError> {
+    if chunk.data.len() < length {
+        return Err(PngError::invalid_chunk(
+            chunk.chunk_type,
+            format!("Expected at least {} bytes, got {}", length, chunk.data.len()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Limit on the decompressed size of ICC profiles and text, so that a small zlib bomb can't exhaust memory.
+pub const MAX_INFLATED_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Splits `bytes` at the next null separator, which is dropped.
+fn split_at_null<'a>(chunk: &ChunkRef, bytes: &'a [u8], field: &str) -> Result<(&'a [u8], &'a [u8]), PngError> {
+    let end = bytes.iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| PngError::invalid_chunk(chunk.chunk_type, format!("{} is not null-terminated", field)))?;
+
+    Ok((&bytes[..end], &bytes[end + 1..]))
+}
+
+/// Converts a byte into one of the `u8_enum`s.
+fn parse_enum<T: TryFrom<u8>>(chunk: &ChunkRef, value: u8, field: &str) -> Result<T, PngError> {
+    T::try_from(value)
+        .map_err(|_| PngError::invalid_chunk(chunk.chunk_type, format!("Invalid {}: {}", field, value)))
+}
+
+fn parse_string(chunk: &ChunkRef, bytes: Vec<u8>, field: &str) -> Result<String, PngError> {
+    String::from_utf8(bytes)
+        .map_err(|_| PngError::invalid_chunk(chunk.chunk_type, format!("{} is not valid UTF-8", field)))
+}
+
+/// https://www.w3.org/TR/png/#11IHDR
+const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IHDR {
     pub width: u32,
     pub height: u32,
-    bit_depth: u8,
+    pub(crate) bit_depth: u8,
     pub color_type: ColorType,
     compression_method: u8,
     filter_method: u8,
-    interlace_method: InterlaceMethod,
+    pub(crate) interlace_method: InterlaceMethod,
 }
 
-impl FromChunk for IHDR {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
-            width: read_be_u32(&chunk.data[..4]),
-            height: read_be_u32(&chunk.data[4..8]),
-            bit_depth: chunk.data[8],
-            color_type: chunk.data[9].try_into().unwrap(),
-            compression_method: chunk.data[10],
-            filter_method: chunk.data[11],
-            interlace_method: chunk.data[12].try_into().unwrap(),
+impl TryFromChunk for IHDR {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 13)?;
+        let (width, height) = (read_be_u32(&chunk.data[..4]), read_be_u32(&chunk.data[4..8]));
+        if width == 0 || height == 0 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Image dimensions must be non-zero"));
+        }
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Image dimensions must not exceed 2^31-1"));
+        }
+
+        let bit_depth = chunk.data[8];
+        let color_type: ColorType = parse_enum(chunk, chunk.data[9], "color type")?;
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(PngError::invalid_chunk(
+                chunk.chunk_type,
+                format!("Bit depth {} is not allowed for {:?}", bit_depth, color_type),
+            ));
+        }
+
+        // 0 is the only method defined for either
+        let (compression_method, filter_method) = (chunk.data[10], chunk.data[11]);
+        if compression_method != 0 {
+            return Err(PngError::UnsupportedFormat(format!("Compression method {}", compression_method)));
+        }
+        if filter_method != 0 {
+            return Err(PngError::UnsupportedFormat(format!("Filter method {}", filter_method)));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method,
+            filter_method,
+            interlace_method: parse_enum(chunk, chunk.data[12], "interlace method")?,
+        })
+    }
+}
+
+impl IHDR {
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn compression_method(&self) -> u8 {
+        self.compression_method
+    }
+
+    pub fn filter_method(&self) -> u8 {
+        self.filter_method
+    }
+
+    pub fn interlace_method(&self) -> InterlaceMethod {
+        self.interlace_method
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.samples_per_pixel() as usize * self.bit_depth as usize
+    }
+
+    /// Length in bytes of a scanline, including the leading filter type byte.
+    pub fn scanline_length(&self) -> Result<usize, PngError> {
+        self.scanline_length_for_width(self.width)
+    }
+
+    /// Length in bytes of a scanline of a reduced image, such as an interlaced pass.
+    pub fn scanline_length_for_width(&self, width: u32) -> Result<usize, PngError> {
+        (width as usize).checked_mul(self.bits_per_pixel())
+            .map(|bits| bits.div_ceil(8) + 1)
+            .ok_or_else(|| self.too_large())
+    }
+
+    /// Length in bytes of the filtered scanlines of an image with the given dimensions.
+    pub(crate) fn filtered_length(&self, width: u32, height: u32) -> Result<usize, PngError> {
+        self.scanline_length_for_width(width)?
+            .checked_mul(height as usize)
+            .ok_or_else(|| self.too_large())
+    }
+
+    /// Length in bytes of all filtered scanlines of the image, across every pass if it is interlaced.
+    pub(crate) fn data_length(&self) -> Result<usize, PngError> {
+        match self.interlace_method {
+            InterlaceMethod::None => { self.filtered_length(self.width, self.height) }
+            InterlaceMethod::Adam7 => {
+                interlace::pass_dimensions(self.width, self.height).into_iter()
+                    // Empty passes don't have any scanlines, not even filter type bytes
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .try_fold(0_usize, |total, (width, height)| {
+                        total.checked_add(self.filtered_length(width, height)?).ok_or_else(|| self.too_large())
+                    })
+            }
+        }
+    }
+
+    /// Length in bytes of a decoded image with the given dimensions and `pixel_bytes` bytes per pixel.
+    pub(crate) fn image_length(&self, width: u32, height: u32, pixel_bytes: usize) -> Result<usize, PngError> {
+        (width as usize).checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(pixel_bytes))
+            .ok_or_else(|| self.too_large())
+    }
+
+    pub(crate) fn too_large(&self) -> PngError {
+        PngError::UnsupportedFormat(format!("Image of {}x{} pixels is too large for this platform", self.width, self.height))
+    }
+
+    /// Bytes per pixel once samples below 8 bits are unpacked into a byte each.
+    pub fn unpacked_bytes_per_pixel(&self) -> usize {
+        self.color_type.samples_per_pixel() as usize * if self.bit_depth == 16 { 2 } else { 1 }
+    }
+
+    /// Distance in bytes to the corresponding byte of the previous pixel, at least 1 for packed pixels.
+    pub fn filter_stride(&self) -> usize {
+        max(1, self.bits_per_pixel() / 8)
+    }
+
+    /// The header of an APNG frame, which shares everything but the dimensions with the default image.
+    pub(crate) fn with_dimensions(&self, width: u32, height: u32) -> IHDR {
+        IHDR {
+            width,
+            height,
+            ..self.clone()
         }
     }
 }
 
 u8_enum! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub enum ColorType {
         Greyscale = 0,
         TrueColor = 2,
@@ -48,7 +218,7 @@ u8_enum! {
 }
 
 impl ColorType {
-    pub fn bytes_per_pixel(&self) -> u32 {
+    pub fn samples_per_pixel(&self) -> u32 {
         match self {
             ColorType::Greyscale => { 1 }
             ColorType::TrueColor => { 3 }
@@ -57,12 +227,29 @@ impl ColorType {
             ColorType::TrueColorAlpha => { 4 }
         }
     }
+
+    #[deprecated(note = "Samples can be smaller or larger than a byte, use `samples_per_pixel`")]
+    pub fn bytes_per_pixel(&self) -> u32 {
+        self.samples_per_pixel()
+    }
+
+    /// https://www.w3.org/TR/png/#table111
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Greyscale => { &[1, 2, 4, 8, 16] }
+            ColorType::TrueColor => { &[8, 16] }
+            ColorType::IndexedColor => { &[1, 2, 4, 8] }
+            ColorType::GreyscaleAlpha => { &[8, 16] }
+            ColorType::TrueColorAlpha => { &[8, 16] }
+        }
+    }
 }
 
 
 
 u8_enum! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub enum InterlaceMethod {
         None = 0,
         Adam7 = 1,
@@ -70,21 +257,31 @@ u8_enum! {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct sRGB {
     intent: RenderingIntent,
 }
 
-impl FromChunk for sRGB {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
-            intent: chunk.data[0].try_into().unwrap()
-        }
+impl TryFromChunk for sRGB {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 1)?;
+
+        Ok(Self {
+            intent: parse_enum(chunk, chunk.data[0], "rendering intent")?
+        })
+    }
+}
+
+impl sRGB {
+    pub fn intent(&self) -> RenderingIntent {
+        self.intent
     }
 }
 
 u8_enum! {
-    #[derive(Debug)]
-    enum RenderingIntent {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum RenderingIntent {
         Perceptual = 0,
         RelativeColorimetric = 1,
         Saturation = 2,
@@ -94,41 +291,75 @@ u8_enum! {
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct gAMA {
-    gamma: f32,
+    gamma: u32,
 }
 
-impl FromChunk for gAMA {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
-            gamma: read_be_u32(&chunk.data) as f32 / 100000.
-        }
+impl TryFromChunk for gAMA {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 4)?;
+
+        Ok(Self {
+            gamma: read_be_u32(chunk.data)
+        })
+    }
+}
+
+impl gAMA {
+    /// The image gamma, e.g. 0.45455
+    pub fn gamma(&self) -> f64 {
+        self.gamma as f64 / 100000.
+    }
+
+    /// The gamma as stored, times 100000.
+    pub fn gamma_raw(&self) -> u32 {
+        self.gamma
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct pHYs {
     pixels_per_unit_x: u32,
     pixels_per_unit_y: u32,
     unit_specifier: PixelUnit,
 }
 
-impl FromChunk for pHYs {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for pHYs {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 9)?;
+
+        Ok(Self {
             pixels_per_unit_x: read_be_u32(&chunk.data[..4]),
             pixels_per_unit_y: read_be_u32(&chunk.data[4..8]),
-            unit_specifier: chunk.data[8].try_into().unwrap(),
-        }
+            unit_specifier: parse_enum(chunk, chunk.data[8], "unit specifier")?,
+        })
+    }
+}
+
+impl pHYs {
+    pub fn pixels_per_unit_x(&self) -> u32 {
+        self.pixels_per_unit_x
+    }
+
+    pub fn pixels_per_unit_y(&self) -> u32 {
+        self.pixels_per_unit_y
+    }
+
+    /// With `PixelUnit::Unknown` only the aspect ratio is known.
+    pub fn unit(&self) -> PixelUnit {
+        self.unit_specifier
     }
 }
 
 
 
 u8_enum! {
-    #[derive(Debug)]
-    enum PixelUnit {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum PixelUnit {
         Unknown = 0,
         Meter = 1,
     }
@@ -136,6 +367,7 @@ u8_enum! {
 
 u8_enum! {
     #[derive(Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub enum FilterType {
         None = 0,
         Sub = 1,
@@ -146,7 +378,8 @@ u8_enum! {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ByteAlign {
     Intel,
     Motorola,
@@ -165,55 +398,68 @@ impl TryFrom<(u8, u8)> for ByteAlign {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct eXIf {
     byte_align: ByteAlign,
-    idfs: Vec<IDF>,
+    ifds: Vec<Ifd>,
 }
 
 #[derive(Debug)]
-pub struct IDF {
-    entries: Vec<Vec<u8>>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ifd {
+    pub(crate) kind: IfdKind,
+    pub(crate) entries: Vec<ExifEntry>,
 }
 
-impl FromChunk for eXIf {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        let mut offset = read_be_u32(&chunk.data[4..8]) as usize;
+impl TryFromChunk for eXIf {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        let (byte_align, ifds) = exif::parse_tiff(chunk.data)?;
 
-        let mut idfs = vec![];
-        while offset > 0 {
-            let idf = IDF::parse_idf(&chunk.data[offset..]);
+        Ok(Self {
+            byte_align,
+            ifds,
+        })
+    }
+}
 
-            offset += 2 + idf.entries.len() * 12; // Offset to next offset
-            idfs.push(idf);
+impl eXIf {
+    pub fn byte_align(&self) -> ByteAlign {
+        self.byte_align
+    }
 
-            offset = read_be_u32(&chunk.data[offset..offset + 4]) as usize;
-        }
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
 
+    pub fn ifd(&self, kind: IfdKind) -> Option<&Ifd> {
+        self.ifds.iter().find(|ifd| ifd.kind == kind)
+    }
 
-        Self {
-            byte_align: ByteAlign::try_from((chunk.data[0], chunk.data[1])).unwrap(),
-            idfs,
-        }
+    /// Looks up a well-known tag of the main image by name, e.g. "Orientation", "DateTimeOriginal" or
+    /// "GPSLatitude".
+    pub fn get(&self, name: &str) -> Option<&ExifEntry> {
+        let (kind, tag) = exif::tag_id(name)?;
+        self.ifd(kind)?.get(tag)
     }
 }
 
-impl IDF {
-    fn parse_idf(bytes: &[u8]) -> IDF {
-        // Consumes a folder off the top of the bytestream
-        let num_entries = u16::from_be_bytes([bytes[0], bytes[1]]);
+impl Ifd {
+    pub fn kind(&self) -> IfdKind {
+        self.kind
+    }
 
-        // Each entry is 12 bytes
-        let entries = bytes[2..2 + (num_entries as usize) * 12]
-            .chunks(12)
-            .map(|e| e.to_vec())
-            .collect::<Vec<_>>();
+    pub fn entries(&self) -> &[ExifEntry] {
+        &self.entries
+    }
 
-        IDF { entries }
+    pub fn get(&self, tag: u16) -> Option<&ExifEntry> {
+        self.entries.iter().find(|entry| entry.tag() == tag)
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct cHRM {
     white_x: u32,
     white_y: u32,
@@ -225,9 +471,11 @@ pub struct cHRM {
     blue_y: u32,
 }
 
-impl FromChunk for cHRM {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for cHRM {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 32)?;
+
+        Ok(Self {
             white_x: read_be_u32(&chunk.data[..4]),
             white_y: read_be_u32(&chunk.data[4..8]),
             red_x: read_be_u32(&chunk.data[8..12]),
@@ -236,74 +484,149 @@ impl FromChunk for cHRM {
             green_y: read_be_u32(&chunk.data[20..24]),
             blue_x: read_be_u32(&chunk.data[24..28]),
             blue_y: read_be_u32(&chunk.data[28..32]),
-        }
+        })
+    }
+}
+
+impl cHRM {
+    /// (x, y) of the white point.
+    pub fn white_point(&self) -> (f64, f64) {
+        cHRM::point(self.white_x, self.white_y)
+    }
+
+    pub fn red(&self) -> (f64, f64) {
+        cHRM::point(self.red_x, self.red_y)
+    }
+
+    pub fn green(&self) -> (f64, f64) {
+        cHRM::point(self.green_x, self.green_y)
+    }
+
+    pub fn blue(&self) -> (f64, f64) {
+        cHRM::point(self.blue_x, self.blue_y)
+    }
+
+    // Stored times 100000
+    fn point(x: u32, y: u32) -> (f64, f64) {
+        (x as f64 / 100000., y as f64 / 100000.)
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct bKGD_Greyscale {
     value: u16,
 }
 
-impl FromChunk for bKGD_Greyscale {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for bKGD_Greyscale {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 2)?;
+
+        Ok(Self {
             value: read_be_u16(&chunk.data[..2]),
-        }
+        })
+    }
+}
+
+impl bKGD_Greyscale {
+    pub fn value(&self) -> u16 {
+        self.value
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct bKGD_TrueColor {
     red: u16,
     green: u16,
     blue: u16,
 }
 
-impl FromChunk for bKGD_TrueColor {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for bKGD_TrueColor {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 6)?;
+
+        Ok(Self {
             red: read_be_u16(&chunk.data[..2]),
             green: read_be_u16(&chunk.data[2..4]),
             blue: read_be_u16(&chunk.data[4..6]),
-        }
+        })
+    }
+}
+
+impl bKGD_TrueColor {
+    pub fn red(&self) -> u16 {
+        self.red
+    }
+
+    pub fn green(&self) -> u16 {
+        self.green
+    }
+
+    pub fn blue(&self) -> u16 {
+        self.blue
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct bKGD_Indexed {
     index: u8,
 }
 
-impl FromChunk for bKGD_Indexed {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for bKGD_Indexed {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 1)?;
+
+        Ok(Self {
             index: chunk.data[0],
-        }
+        })
+    }
+}
+
+impl bKGD_Indexed {
+    /// Index into PLTE.
+    pub fn index(&self) -> u8 {
+        self.index
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct tEXt {
     keyword: String,
     text: String,
 }
 
-impl FromChunk for tEXt {
-    fn from_chunk(chunk: &Chunk) -> Self {
+impl TryFromChunk for tEXt {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
         // Split on first null byte
         let split = chunk.data.splitn(2, |&x| x == 0)
             .collect::<Vec<_>>();
-
-        Self {
-            keyword: String::from_utf8(split[0].to_vec()).unwrap(),
-            text: String::from_utf8(split[1].to_vec()).unwrap(),
+        if split.len() < 2 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Missing null separator"));
         }
+
+        Ok(Self {
+            keyword: parse_string(chunk, split[0].to_vec(), "Keyword")?,
+            text: parse_string(chunk, split[1].to_vec(), "Text")?,
+        })
+    }
+}
+
+impl tEXt {
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct iCCP {
     profile_name: String,
     compression_method: CompressionMethod,
@@ -312,34 +635,59 @@ pub struct iCCP {
 
 
 u8_enum! {
-    #[derive(Debug)]
-    enum CompressionMethod {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum CompressionMethod {
         ZLIB = 0,
     }
 }
 
-impl FromChunk for iCCP {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        let name = read_until_null(&chunk.data);
+impl TryFromChunk for iCCP {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        let name = read_until_null(chunk.data);
         let name_len = name.len();
+        check_length(chunk, name_len + 2)?;
+        let compression_method = parse_enum(chunk, chunk.data[name_len + 1], "compression method")?;
 
-        // Decompress todo
-        // let mut decompressed = vec![];
-        // zlib::Decoder::new(&chunk.data[name_len + 2..])
-        //     .read_to_end(&mut decompressed)
-        //     .expect("Failed to decompress byte stream");
+        let profile = inflate(&chunk.data[name_len + 2..], MAX_INFLATED_LENGTH)?;
 
-
-        Self {
-            profile_name: String::from_utf8(name).unwrap(),
-            compression_method: chunk.data[name_len + 1].try_into().unwrap(),
-            profile: chunk.data[name_len + 2..].to_vec(),
+        // Check the ICC header, https://www.color.org/specification/ICC.1-2022-05.pdf section 7.2
+        if profile.len() < 128 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "ICC profile is shorter than its 128 byte header"));
+        }
+        if read_be_u32(&profile[..4]) as usize != profile.len() {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "ICC profile size does not match its header"));
         }
+        if &profile[36..40] != b"acsp" {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "ICC profile is missing the 'acsp' signature"));
+        }
+
+        Ok(Self {
+            profile_name: parse_string(chunk, name, "Profile name")?,
+            compression_method,
+            profile,
+        })
+    }
+}
+
+impl iCCP {
+    pub fn profile_name(&self) -> &str {
+        &self.profile_name
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
+
+    /// The decompressed ICC profile, ready to be handed to a CMS.
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct tIME {
     year: u16,
     month: u8,
@@ -349,21 +697,50 @@ pub struct tIME {
     second: u8,
 }
 
-impl FromChunk for tIME {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for tIME {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 7)?;
+
+        Ok(Self {
             year: read_be_u16(&chunk.data[..2]),
             month: chunk.data[2],
             day: chunk.data[3],
             hour: chunk.data[4],
             minute: chunk.data[5],
             second: chunk.data[6],
-        }
+        })
+    }
+}
+
+impl tIME {
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct zTXt {
     keyword: String,
     compression_method: CompressionMethod,
@@ -376,89 +753,150 @@ impl Display for zTXt {
     }
 }
 
-impl FromChunk for zTXt {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        let name = read_until_null(&chunk.data);
+impl TryFromChunk for zTXt {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        let name = read_until_null(chunk.data);
 
         let name_len = name.len();
+        check_length(chunk, name_len + 2)?;
+        let compression_method = parse_enum(chunk, chunk.data[name_len + 1], "compression method")?;
 
-        // Decompress
-        let mut decompressed = vec![];
-        zlib::Decoder::new(&chunk.data[name_len + 2..])
-            .read_to_end(&mut decompressed)
-            .expect("Failed to decompress byte stream");
+        let decompressed = inflate(&chunk.data[name_len + 2..], MAX_INFLATED_LENGTH)?;
 
-        Self {
-            keyword: String::from_utf8(name).unwrap(),
-            compression_method: chunk.data[name_len + 1].try_into().unwrap(),
-            text: String::from_utf8(decompressed).unwrap(),
-        }
+        Ok(Self {
+            keyword: parse_string(chunk, name, "Keyword")?,
+            compression_method,
+            text: parse_string(chunk, decompressed, "Text")?,
+        })
+    }
+}
+
+impl zTXt {
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
+
+    /// The decompressed text.
+    pub fn text(&self) -> &str {
+        &self.text
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PLTE {
-    palette: Vec<Vec<u8>>,
+    palette: Vec<[u8; 3]>,
 }
 
-impl FromChunk for PLTE {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for PLTE {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        if !chunk.data.len().is_multiple_of(3) {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Length is not a multiple of 3"));
+        }
+
+        Ok(Self {
             palette: chunk.data.chunks(3)
-                .map(|c| c.to_vec())
+                .map(|c| [c[0], c[1], c[2]])
                 .collect()
-        }
+        })
+    }
+}
+
+impl PLTE {
+    /// RGB entries, indexed by the pixels of indexed color images.
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.palette
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct tRNS_Greyscale {
     value: u16,
 }
 
-impl FromChunk for tRNS_Greyscale {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for tRNS_Greyscale {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 2)?;
+
+        Ok(Self {
             value: read_be_u16(&chunk.data[..2]),
-        }
+        })
+    }
+}
+
+impl tRNS_Greyscale {
+    /// Pixels with this grey level are fully transparent.
+    pub fn value(&self) -> u16 {
+        self.value
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct tRNS_TrueColor {
     red: u16,
-    blue: u16,
     green: u16,
+    blue: u16,
 }
 
-impl FromChunk for tRNS_TrueColor {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for tRNS_TrueColor {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 6)?;
+
+        Ok(Self {
             red: read_be_u16(&chunk.data[..2]),
-            blue: read_be_u16(&chunk.data[2..4]),
-            green: read_be_u16(&chunk.data[4..6]),
-        }
+            green: read_be_u16(&chunk.data[2..4]),
+            blue: read_be_u16(&chunk.data[4..6]),
+        })
+    }
+}
+
+impl tRNS_TrueColor {
+    pub fn red(&self) -> u16 {
+        self.red
+    }
+
+    pub fn green(&self) -> u16 {
+        self.green
+    }
+
+    pub fn blue(&self) -> u16 {
+        self.blue
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct tRNS_Indexed {
     values: Vec<u8>,
 }
 
-impl FromChunk for tRNS_Indexed {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        Self {
+impl TryFromChunk for tRNS_Indexed {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        Ok(Self {
             values: chunk.data.to_vec()
-        }
+        })
+    }
+}
+
+impl tRNS_Indexed {
+    /// Alpha for the first PLTE entries, the remaining entries are opaque.
+    pub fn values(&self) -> &[u8] {
+        &self.values
     }
 }
 
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct iTXt {
     keyword: String,
     is_compressed: bool,
@@ -468,48 +906,349 @@ pub struct iTXt {
     text: String,
 }
 
-impl FromChunk for iTXt {
-    fn from_chunk(chunk: &Chunk) -> Self {
-        let keyword = read_until_null(&chunk.data);
-        let mut offset = keyword.len() + 1;
+impl TryFromChunk for iTXt {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        // Keyword, compression flag and method, language tag, translated keyword and text, in that order
+        let (keyword, rest) = split_at_null(chunk, chunk.data, "Keyword")?;
+        if rest.len() < 2 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Missing compression flag and method"));
+        }
+
+        let is_compressed = match rest[0] {
+            0 => { false }
+            1 => { true }
+            flag => { return Err(PngError::invalid_chunk(chunk.chunk_type, format!("Invalid compression flag: {}", flag))) }
+        };
+        let compression_method = parse_enum(chunk, rest[1], "compression method")?;
 
-        let is_compressed = chunk.data[offset] == 1;
-        let compression_method = CompressionMethod::try_from(chunk.data[offset + 1]).unwrap();
-        offset += 2;
+        // Language tag, e.g. "en" or "x-klingon"
+        let (lang_tag, rest) = split_at_null(chunk, &rest[2..], "Language tag")?;
+        if !lang_tag.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'-') {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Language tag must consist of ASCII letters, digits and hyphens"));
+        }
+
+        let (translated_keyword, text) = split_at_null(chunk, rest, "Translated keyword")?;
 
-        // Language tag
-        let lang_tag = read_until_null(&chunk.data[offset..]);
-        let lang_tag = if lang_tag.is_empty() {
-            None
+        // The text runs to the end of the chunk
+        let text = if is_compressed {
+            inflate(text, MAX_INFLATED_LENGTH)?
         } else {
-            offset += lang_tag.len();
-            Some(String::from_utf8(lang_tag).unwrap())
+            text.to_vec()
         };
-        offset += 1;
-
-        // Translated keyword
-        let translated_keyword = match lang_tag {
-            None => { None }
-            Some(_) => {
-                let bytes = read_until_null(&chunk.data[offset..]);
-                offset += bytes.len();
 
-                Some(String::from_utf8(bytes).unwrap())
-            }
+        // Empty language tags and translated keywords are left out
+        let optional = |bytes: &[u8], field: &str| if bytes.is_empty() {
+            Ok(None)
+        } else {
+            parse_string(chunk, bytes.to_vec(), field).map(Some)
         };
 
+        Ok(Self {
+            keyword: parse_string(chunk, keyword.to_vec(), "Keyword")?,
+            is_compressed,
+            compression_method,
+            lang_tag: optional(lang_tag, "Language tag")?,
+            translated_keyword: optional(translated_keyword, "Translated keyword")?,
+            text: parse_string(chunk, text, "Text")?,
+        })
+    }
+}
+
+impl iTXt {
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
 
-        offset += 1;
+    pub fn is_compressed(&self) -> bool {
+        self.is_compressed
+    }
 
-        let text = read_until_null(&chunk.data[offset..]);
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
 
-        Self {
-            keyword: String::from_utf8(keyword).unwrap(),
-            is_compressed,
-            compression_method,
-            lang_tag,
-            translated_keyword,
-            text: String::from_utf8(text).unwrap(),
+    pub fn language_tag(&self) -> Option<&str> {
+        self.lang_tag.as_deref()
+    }
+
+    pub fn translated_keyword(&self) -> Option<&str> {
+        self.translated_keyword.as_deref()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+
+// APNG chunks, https://wiki.mozilla.org/APNG_Specification
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct acTL {
+    num_frames: u32,
+    num_plays: u32,
+}
+
+impl TryFromChunk for acTL {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 8)?;
+        let num_frames = read_be_u32(&chunk.data[..4]);
+        if num_frames == 0 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Number of frames must be non-zero"));
         }
+
+        Ok(Self {
+            num_frames,
+            num_plays: read_be_u32(&chunk.data[4..8]),
+        })
+    }
+}
+
+impl acTL {
+    pub fn num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    /// Number of times to loop the animation, 0 means forever.
+    pub fn num_plays(&self) -> u32 {
+        self.num_plays
+    }
+}
+
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct fcTL {
+    sequence_number: u32,
+    width: u32,
+    height: u32,
+    x_offset: u32,
+    y_offset: u32,
+    delay_num: u16,
+    delay_den: u16,
+    dispose_op: DisposeOp,
+    blend_op: BlendOp,
+}
+
+u8_enum! {
+    /// What happens to the frame's region of the canvas before the next frame is rendered.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum DisposeOp {
+        /// Leave the canvas as it is.
+        None = 0,
+        /// Clear the region to fully transparent black.
+        Background = 1,
+        /// Restore the region to what it was before this frame.
+        Previous = 2,
+    }
+}
+
+u8_enum! {
+    /// How the frame is combined with the canvas.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum BlendOp {
+        /// Replace the region, including alpha.
+        Source = 0,
+        /// Alpha composite the frame over the region.
+        Over = 1,
+    }
+}
+
+impl TryFromChunk for fcTL {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 26)?;
+        if read_be_u32(&chunk.data[4..8]) == 0 || read_be_u32(&chunk.data[8..12]) == 0 {
+            return Err(PngError::invalid_chunk(chunk.chunk_type, "Frame dimensions must be non-zero"));
+        }
+
+        Ok(Self {
+            sequence_number: read_be_u32(&chunk.data[..4]),
+            width: read_be_u32(&chunk.data[4..8]),
+            height: read_be_u32(&chunk.data[8..12]),
+            x_offset: read_be_u32(&chunk.data[12..16]),
+            y_offset: read_be_u32(&chunk.data[16..20]),
+            delay_num: read_be_u16(&chunk.data[20..22]),
+            delay_den: read_be_u16(&chunk.data[22..24]),
+            dispose_op: parse_enum(chunk, chunk.data[24], "dispose op")?,
+            blend_op: parse_enum(chunk, chunk.data[25], "blend op")?,
+        })
+    }
+}
+
+impl fcTL {
+    /// Control for a PNG without animation, showing the default image once.
+    pub(crate) fn for_default_image(ihdr: &IHDR) -> fcTL {
+        fcTL {
+            sequence_number: 0,
+            width: ihdr.width,
+            height: ihdr.height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 0,
+            delay_den: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+    }
+
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
     }
-}
\ No newline at end of file
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn x_offset(&self) -> u32 {
+        self.x_offset
+    }
+
+    pub fn y_offset(&self) -> u32 {
+        self.y_offset
+    }
+
+    /// Numerator and denominator of the frame delay in seconds.
+    pub fn delay_fraction(&self) -> (u16, u16) {
+        (self.delay_num, self.delay_den)
+    }
+
+    /// How long the frame is shown, a denominator of 0 means hundredths of a second.
+    pub fn delay(&self) -> Duration {
+        let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+        Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+    }
+
+    pub fn dispose_op(&self) -> DisposeOp {
+        self.dispose_op
+    }
+
+    pub fn blend_op(&self) -> BlendOp {
+        self.blend_op
+    }
+}
+
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct fdAT {
+    sequence_number: u32,
+    frame_data: Vec<u8>,
+}
+
+impl TryFromChunk for fdAT {
+    fn try_from_chunk_ref(chunk: &ChunkRef) -> Result<Self, PngError> {
+        check_length(chunk, 4)?;
+
+        Ok(Self {
+            sequence_number: read_be_u32(&chunk.data[..4]),
+            frame_data: chunk.data[4..].to_vec(),
+        })
+    }
+}
+
+impl fdAT {
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Compressed image data, continuing the stream of the previous fdAT of the same frame like IDAT does.
+    pub fn frame_data(&self) -> &[u8] {
+        &self.frame_data
+    }
+}
+
+
+/// A chunk parsed into the struct for its type, see `PNG::parsed_chunks`.
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParsedChunk {
+    IHDR(IHDR),
+    PLTE(PLTE),
+    /// Compressed image data, see `PNG::decode` for decoding it.
+    IDAT(Vec<u8>),
+    IEND,
+    sRGB(sRGB),
+    gAMA(gAMA),
+    pHYs(pHYs),
+    eXIf(eXIf),
+    cHRM(cHRM),
+    bKGD_Greyscale(bKGD_Greyscale),
+    bKGD_TrueColor(bKGD_TrueColor),
+    bKGD_Indexed(bKGD_Indexed),
+    tEXt(tEXt),
+    zTXt(zTXt),
+    iTXt(iTXt),
+    iCCP(iCCP),
+    tIME(tIME),
+    tRNS_Greyscale(tRNS_Greyscale),
+    tRNS_TrueColor(tRNS_TrueColor),
+    tRNS_Indexed(tRNS_Indexed),
+    acTL(acTL),
+    fcTL(fcTL),
+    fdAT(fdAT),
+    /// A chunk type this crate does not know, left as it is.
+    Unknown(Chunk),
+}
+
+impl ParsedChunk {
+    /// Parses a chunk by its type. The layout of bKGD and tRNS depends on the color type from IHDR, so they
+    /// fail with `MissingChunk` if `color_type` is None.
+    pub fn parse(chunk: &ChunkRef, color_type: Option<ColorType>) -> Result<ParsedChunk, PngError> {
+        let color_type = || color_type.ok_or(PngError::MissingChunk(String::from("IHDR")));
+
+        let parsed = match chunk.chunk_type {
+            "IHDR" => { ParsedChunk::IHDR(IHDR::try_from_chunk_ref(chunk)?) }
+            "PLTE" => { ParsedChunk::PLTE(PLTE::try_from_chunk_ref(chunk)?) }
+            "IDAT" => { ParsedChunk::IDAT(chunk.data.to_vec()) }
+            "IEND" => { ParsedChunk::IEND }
+            "sRGB" => { ParsedChunk::sRGB(sRGB::try_from_chunk_ref(chunk)?) }
+            "gAMA" => { ParsedChunk::gAMA(gAMA::try_from_chunk_ref(chunk)?) }
+            "pHYs" => { ParsedChunk::pHYs(pHYs::try_from_chunk_ref(chunk)?) }
+            "eXIf" => { ParsedChunk::eXIf(eXIf::try_from_chunk_ref(chunk)?) }
+            "cHRM" => { ParsedChunk::cHRM(cHRM::try_from_chunk_ref(chunk)?) }
+            "bKGD" => {
+                match color_type()? {
+                    ColorType::Greyscale | ColorType::GreyscaleAlpha => {
+                        ParsedChunk::bKGD_Greyscale(bKGD_Greyscale::try_from_chunk_ref(chunk)?)
+                    }
+                    ColorType::TrueColor | ColorType::TrueColorAlpha => {
+                        ParsedChunk::bKGD_TrueColor(bKGD_TrueColor::try_from_chunk_ref(chunk)?)
+                    }
+                    ColorType::IndexedColor => { ParsedChunk::bKGD_Indexed(bKGD_Indexed::try_from_chunk_ref(chunk)?) }
+                }
+            }
+            "tEXt" => { ParsedChunk::tEXt(tEXt::try_from_chunk_ref(chunk)?) }
+            "zTXt" => { ParsedChunk::zTXt(zTXt::try_from_chunk_ref(chunk)?) }
+            "iTXt" => { ParsedChunk::iTXt(iTXt::try_from_chunk_ref(chunk)?) }
+            "iCCP" => { ParsedChunk::iCCP(iCCP::try_from_chunk_ref(chunk)?) }
+            "tIME" => { ParsedChunk::tIME(tIME::try_from_chunk_ref(chunk)?) }
+            "tRNS" => {
+                match color_type()? {
+                    ColorType::Greyscale => { ParsedChunk::tRNS_Greyscale(tRNS_Greyscale::try_from_chunk_ref(chunk)?) }
+                    ColorType::TrueColor => { ParsedChunk::tRNS_TrueColor(tRNS_TrueColor::try_from_chunk_ref(chunk)?) }
+                    ColorType::IndexedColor => { ParsedChunk::tRNS_Indexed(tRNS_Indexed::try_from_chunk_ref(chunk)?) }
+                    color_type => {
+                        return Err(PngError::invalid_chunk(
+                            chunk.chunk_type,
+                            format!("Not allowed for {:?}, which already has an alpha channel", color_type),
+                        ));
+                    }
+                }
+            }
+            "acTL" => { ParsedChunk::acTL(acTL::try_from_chunk_ref(chunk)?) }
+            "fcTL" => { ParsedChunk::fcTL(fcTL::try_from_chunk_ref(chunk)?) }
+            "fdAT" => { ParsedChunk::fdAT(fdAT::try_from_chunk_ref(chunk)?) }
+            _ => { ParsedChunk::Unknown(chunk.to_owned()) }
+        };
+
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+#[derive(Debug)]
+pub enum PngError {
+    /// The underlying reader or file failed.
+    Io(io::Error),
+    /// The first 8 bytes are not the PNG signature.
+    BadSignature(Vec<u8>),
+    /// The byte stream ended before a complete structure could be read.
+    Truncated,
+    /// The stored CRC of a chunk does not match its contents.
+    BadCrc {
+        chunk_type: String,
+        stored: u32,
+        computed: u32,
+    },
+    /// A chunk is malformed or contains an illegal value.
+    InvalidChunk {
+        chunk_type: String,
+        reason: String,
+    },
+    /// A chunk required for the requested operation is not present.
+    MissingChunk(String),
+    /// A pixel refers to a palette entry that does not exist.
+    PaletteIndexOutOfRange {
+        index: u8,
+        palette_length: usize,
+    },
+    /// A zlib stream could not be inflated.
+    Decompress(String),
+    /// The image is valid but uses a feature this crate cannot decode.
+    UnsupportedFormat(String),
+    /// An argument does not fit the image it is used with.
+    InvalidArgument(String),
+}
+
+impl PngError {
+    pub(crate) fn invalid_chunk(chunk_type: &str, reason: impl Into<String>) -> PngError {
+        PngError::InvalidChunk {
+            chunk_type: String::from(chunk_type),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::Io(e) => { write!(f, "I/O error: {}", e) }
+            PngError::BadSignature(header) => {
+                write!(f, "Invalid PNG header: {:?}", header.iter().map(|x| format!("{x:X}")).collect::<Vec<String>>())
+            }
+            PngError::Truncated => { write!(f, "Unexpected end of data") }
+            PngError::BadCrc { chunk_type, stored, computed } => {
+                write!(f, "CRC mismatch in {} chunk (stored: {:08X}, computed: {:08X})", chunk_type, stored, computed)
+            }
+            PngError::InvalidChunk { chunk_type, reason } => { write!(f, "Invalid {} chunk: {}", chunk_type, reason) }
+            PngError::MissingChunk(chunk_type) => { write!(f, "Missing {} chunk", chunk_type) }
+            PngError::PaletteIndexOutOfRange { index, palette_length } => {
+                write!(f, "Palette index {} out of range for palette of length {}", index, palette_length)
+            }
+            PngError::Decompress(reason) => { write!(f, "Failed to decompress byte stream: {}", reason) }
+            PngError::UnsupportedFormat(reason) => { write!(f, "Unsupported format: {}", reason) }
+            PngError::InvalidArgument(reason) => { write!(f, "Invalid argument: {}", reason) }
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::Io(e) => { Some(e) }
+            _ => { None }
+        }
+    }
+}
+
+impl From<io::Error> for PngError {
+    fn from(e: io::Error) -> Self {
+        PngError::Io(e)
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+
+use crate::chunks::{ByteAlign, Ifd};
+use crate::error::PngError;
+
+/// The IFD an entry belongs to, tag ids are only unique within one kind of IFD.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IfdKind {
+    /// IFD0, describing the main image.
+    Primary,
+    /// IFD1, describing the embedded thumbnail.
+    Thumbnail,
+    Exif,
+    Gps,
+    Interoperability,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExifEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value: ExifValue,
+}
+
+impl ExifEntry {
+    pub fn tag(&self) -> u16 {
+        self.tag
+    }
+
+    /// The TIFF field type, e.g. 2 for ASCII or 5 for RATIONAL.
+    pub fn field_type(&self) -> u16 {
+        self.field_type
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn value(&self) -> &ExifValue {
+        &self.value
+    }
+}
+
+/// The values of an entry, resolved from the entry itself or from its offset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    /// Text with the trailing null bytes removed.
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    /// Numerator and denominator pairs.
+    Rational(Vec<(u32, u32)>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl ExifValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ExifValue::Ascii(text) => { Some(text) }
+            _ => { None }
+        }
+    }
+
+    /// The first value of an unsigned integer entry.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Byte(values) => { values.first().map(|&v| v as u32) }
+            ExifValue::Short(values) => { values.first().map(|&v| v as u32) }
+            ExifValue::Long(values) => { values.first().copied() }
+            _ => { None }
+        }
+    }
+
+    /// All values of a numeric entry as floating point, with rationals divided out.
+    pub fn to_f64s(&self) -> Option<Vec<f64>> {
+        let values = match self {
+            ExifValue::Byte(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::Short(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::Long(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::Rational(values) => { values.iter().map(|&(n, d)| n as f64 / d as f64).collect() }
+            ExifValue::SByte(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::SShort(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::SLong(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::SRational(values) => { values.iter().map(|&(n, d)| n as f64 / d as f64).collect() }
+            ExifValue::Float(values) => { values.iter().map(|&v| v as f64).collect() }
+            ExifValue::Double(values) => { values.clone() }
+            ExifValue::Ascii(_) | ExifValue::Undefined(_) => { return None }
+        };
+
+        Some(values)
+    }
+}
+
+
+// Well-known tags, from the Exif 2.32 specification (CIPA DC-008)
+const TAGS: &[(IfdKind, u16, &str)] = &[
+    // TIFF
+    (IfdKind::Primary, 0x0100, "ImageWidth"),
+    (IfdKind::Primary, 0x0101, "ImageLength"),
+    (IfdKind::Primary, 0x0102, "BitsPerSample"),
+    (IfdKind::Primary, 0x0103, "Compression"),
+    (IfdKind::Primary, 0x0106, "PhotometricInterpretation"),
+    (IfdKind::Primary, 0x010E, "ImageDescription"),
+    (IfdKind::Primary, 0x010F, "Make"),
+    (IfdKind::Primary, 0x0110, "Model"),
+    (IfdKind::Primary, 0x0112, "Orientation"),
+    (IfdKind::Primary, 0x011A, "XResolution"),
+    (IfdKind::Primary, 0x011B, "YResolution"),
+    (IfdKind::Primary, 0x0128, "ResolutionUnit"),
+    (IfdKind::Primary, 0x0131, "Software"),
+    (IfdKind::Primary, 0x0132, "DateTime"),
+    (IfdKind::Primary, 0x013B, "Artist"),
+    (IfdKind::Primary, 0x0201, "JPEGInterchangeFormat"),
+    (IfdKind::Primary, 0x0202, "JPEGInterchangeFormatLength"),
+    (IfdKind::Primary, 0x0213, "YCbCrPositioning"),
+    (IfdKind::Primary, 0x8298, "Copyright"),
+    (IfdKind::Primary, 0x8769, "ExifIFDPointer"),
+    (IfdKind::Primary, 0x8825, "GPSInfoIFDPointer"),
+    // Exif
+    (IfdKind::Exif, 0x829A, "ExposureTime"),
+    (IfdKind::Exif, 0x829D, "FNumber"),
+    (IfdKind::Exif, 0x8822, "ExposureProgram"),
+    (IfdKind::Exif, 0x8827, "PhotographicSensitivity"),
+    (IfdKind::Exif, 0x9000, "ExifVersion"),
+    (IfdKind::Exif, 0x9003, "DateTimeOriginal"),
+    (IfdKind::Exif, 0x9004, "DateTimeDigitized"),
+    (IfdKind::Exif, 0x9010, "OffsetTime"),
+    (IfdKind::Exif, 0x9011, "OffsetTimeOriginal"),
+    (IfdKind::Exif, 0x9101, "ComponentsConfiguration"),
+    (IfdKind::Exif, 0x9201, "ShutterSpeedValue"),
+    (IfdKind::Exif, 0x9202, "ApertureValue"),
+    (IfdKind::Exif, 0x9204, "ExposureBiasValue"),
+    (IfdKind::Exif, 0x9207, "MeteringMode"),
+    (IfdKind::Exif, 0x9209, "Flash"),
+    (IfdKind::Exif, 0x920A, "FocalLength"),
+    (IfdKind::Exif, 0x927C, "MakerNote"),
+    (IfdKind::Exif, 0x9286, "UserComment"),
+    (IfdKind::Exif, 0xA000, "FlashpixVersion"),
+    (IfdKind::Exif, 0xA001, "ColorSpace"),
+    (IfdKind::Exif, 0xA002, "PixelXDimension"),
+    (IfdKind::Exif, 0xA003, "PixelYDimension"),
+    (IfdKind::Exif, 0xA005, "InteroperabilityIFDPointer"),
+    (IfdKind::Exif, 0xA402, "ExposureMode"),
+    (IfdKind::Exif, 0xA403, "WhiteBalance"),
+    (IfdKind::Exif, 0xA405, "FocalLengthIn35mmFilm"),
+    (IfdKind::Exif, 0xA406, "SceneCaptureType"),
+    (IfdKind::Exif, 0xA420, "ImageUniqueID"),
+    (IfdKind::Exif, 0xA430, "CameraOwnerName"),
+    (IfdKind::Exif, 0xA431, "BodySerialNumber"),
+    (IfdKind::Exif, 0xA433, "LensMake"),
+    (IfdKind::Exif, 0xA434, "LensModel"),
+    // GPS
+    (IfdKind::Gps, 0x0000, "GPSVersionID"),
+    (IfdKind::Gps, 0x0001, "GPSLatitudeRef"),
+    (IfdKind::Gps, 0x0002, "GPSLatitude"),
+    (IfdKind::Gps, 0x0003, "GPSLongitudeRef"),
+    (IfdKind::Gps, 0x0004, "GPSLongitude"),
+    (IfdKind::Gps, 0x0005, "GPSAltitudeRef"),
+    (IfdKind::Gps, 0x0006, "GPSAltitude"),
+    (IfdKind::Gps, 0x0007, "GPSTimeStamp"),
+    (IfdKind::Gps, 0x000C, "GPSSpeedRef"),
+    (IfdKind::Gps, 0x000D, "GPSSpeed"),
+    (IfdKind::Gps, 0x0010, "GPSImgDirectionRef"),
+    (IfdKind::Gps, 0x0011, "GPSImgDirection"),
+    (IfdKind::Gps, 0x0012, "GPSMapDatum"),
+    (IfdKind::Gps, 0x001D, "GPSDateStamp"),
+    // Interoperability
+    (IfdKind::Interoperability, 0x0001, "InteroperabilityIndex"),
+];
+
+/// The name of a well-known tag. Thumbnail entries share their names with the primary image.
+pub fn tag_name(kind: IfdKind, tag: u16) -> Option<&'static str> {
+    let kind = if kind == IfdKind::Thumbnail { IfdKind::Primary } else { kind };
+    TAGS.iter()
+        .find(|&&(k, t, _)| k == kind && t == tag)
+        .map(|&(_, _, name)| name)
+}
+
+/// The IFD and tag id of a well-known tag name.
+pub fn tag_id(name: &str) -> Option<(IfdKind, u16)> {
+    TAGS.iter()
+        .find(|&&(_, _, n)| n == name)
+        .map(|&(kind, tag, _)| (kind, tag))
+}
+
+
+/// Size in bytes of a single value of a TIFF field type, None for unknown types.
+fn type_size(field_type: u16) -> Option<u64> {
+    match field_type {
+        1 | 2 | 6 | 7 => { Some(1) }
+        3 | 8 => { Some(2) }
+        4 | 9 | 11 => { Some(4) }
+        5 | 10 | 12 => { Some(8) }
+        _ => { None }
+    }
+}
+
+fn invalid(reason: &str) -> PngError {
+    PngError::invalid_chunk("eXIf", reason)
+}
+
+/// Reads integers from the TIFF structure in the byte order given by its header.
+struct TiffReader<'a> {
+    data: &'a [u8],
+    byte_align: ByteAlign,
+    // Offsets of the IFDs read so far, to stop offsets that loop
+    visited: HashSet<usize>,
+    // Entries and out of line values read so far. They don't overlap in a valid file, so neither can add up to
+    // more than the chunk itself.
+    entry_bytes: usize,
+    value_bytes: usize,
+}
+
+impl<'a> TiffReader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], PngError> {
+        offset.checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| invalid("Entry extends past end of chunk"))
+    }
+
+    fn to_u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self.byte_align {
+            ByteAlign::Intel => { u16::from_le_bytes(bytes) }
+            ByteAlign::Motorola => { u16::from_be_bytes(bytes) }
+        }
+    }
+
+    fn to_u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self.byte_align {
+            ByteAlign::Intel => { u32::from_le_bytes(bytes) }
+            ByteAlign::Motorola => { u32::from_be_bytes(bytes) }
+        }
+    }
+
+    fn to_u64(&self, bytes: &[u8]) -> u64 {
+        let (first, second) = (self.to_u32(&bytes[..4]) as u64, self.to_u32(&bytes[4..8]) as u64);
+        match self.byte_align {
+            ByteAlign::Intel => { second << 32 | first }
+            ByteAlign::Motorola => { first << 32 | second }
+        }
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, PngError> {
+        Ok(self.to_u16(self.bytes(offset, 2)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, PngError> {
+        Ok(self.to_u32(self.bytes(offset, 4)?))
+    }
+
+    fn value(&self, field_type: u16, bytes: &[u8]) -> ExifValue {
+        let rational = |b: &[u8]| (self.to_u32(&b[..4]), self.to_u32(&b[4..]));
+
+        match field_type {
+            1 => { ExifValue::Byte(bytes.to_vec()) }
+            2 => { ExifValue::Ascii(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()) }
+            3 => { ExifValue::Short(bytes.chunks_exact(2).map(|b| self.to_u16(b)).collect()) }
+            4 => { ExifValue::Long(bytes.chunks_exact(4).map(|b| self.to_u32(b)).collect()) }
+            5 => { ExifValue::Rational(bytes.chunks_exact(8).map(rational).collect()) }
+            6 => { ExifValue::SByte(bytes.iter().map(|&b| b as i8).collect()) }
+            8 => { ExifValue::SShort(bytes.chunks_exact(2).map(|b| self.to_u16(b) as i16).collect()) }
+            9 => { ExifValue::SLong(bytes.chunks_exact(4).map(|b| self.to_u32(b) as i32).collect()) }
+            10 => {
+                ExifValue::SRational(bytes.chunks_exact(8).map(|b| {
+                    let (n, d) = rational(b);
+                    (n as i32, d as i32)
+                }).collect())
+            }
+            11 => { ExifValue::Float(bytes.chunks_exact(4).map(|b| f32::from_bits(self.to_u32(b))).collect()) }
+            12 => { ExifValue::Double(bytes.chunks_exact(8).map(|b| f64::from_bits(self.to_u64(b))).collect()) }
+            _ => { ExifValue::Undefined(bytes.to_vec()) }
+        }
+    }
+
+    /// Reads the IFD at `offset`, returning it with the position of its next IFD offset.
+    fn ifd(&mut self, offset: usize, kind: IfdKind) -> Result<(Ifd, usize), PngError> {
+        if offset < 8 {
+            return Err(invalid("Invalid IFD offset"));
+        }
+        if !self.visited.insert(offset) {
+            return Err(invalid("IFD offsets loop"));
+        }
+
+        let num_entries = self.u16(offset)? as usize;
+        self.entry_bytes += num_entries * 12;
+        if self.entry_bytes > self.data.len() {
+            return Err(invalid("IFD entries exceed the length of the chunk"));
+        }
+
+        let mut entries = Vec::with_capacity(num_entries);
+        for i in 0..num_entries {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            let field_type = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)?;
+
+            // Entries of unknown types can't be read as their size is unknown
+            let size = match type_size(field_type) {
+                Some(size) => { size * count as u64 }
+                None => { continue }
+            };
+
+            // Values of up to 4 bytes are stored in place of the offset
+            let value_offset = if size <= 4 {
+                entry + 8
+            } else {
+                if size > (self.data.len() - self.value_bytes) as u64 {
+                    return Err(invalid("Entry values exceed the length of the chunk"));
+                }
+                self.value_bytes += size as usize;
+                self.u32(entry + 8)? as usize
+            };
+            let bytes = self.bytes(value_offset, size as usize)?;
+
+            entries.push(ExifEntry {
+                tag,
+                field_type,
+                count,
+                value: self.value(field_type, bytes),
+            });
+        }
+
+        Ok((Ifd { kind, entries }, offset + 2 + num_entries * 12))
+    }
+}
+
+/// Parses the TIFF structure of an eXIf chunk: IFD0, the thumbnail IFD chained to it, and the Exif, GPS and
+/// Interoperability IFDs they point to.
+pub(crate) fn parse_tiff(data: &[u8]) -> Result<(ByteAlign, Vec<Ifd>), PngError> {
+    if data.len() < 8 {
+        return Err(invalid("TIFF header is shorter than 8 bytes"));
+    }
+    let byte_align = ByteAlign::try_from((data[0], data[1]))
+        .map_err(|_| invalid("Invalid byte alignment"))?;
+    let mut reader = TiffReader {
+        data,
+        byte_align,
+        visited: HashSet::new(),
+        entry_bytes: 0,
+        value_bytes: 0,
+    };
+    if reader.u16(2)? != 42 {
+        return Err(invalid("Invalid TIFF magic number"));
+    }
+
+    let mut ifds = vec![];
+    let mut offset = reader.u32(4)? as usize;
+    let mut kind = IfdKind::Primary;
+    while offset > 0 {
+        let (ifd, next) = reader.ifd(offset, kind)?;
+        ifds.push(ifd);
+        offset = reader.u32(next)? as usize;
+        kind = IfdKind::Thumbnail;
+    }
+
+    // Sub-IFDs, each at the offset stored in an entry of its parent
+    let pointers = [
+        (IfdKind::Primary, 0x8769, IfdKind::Exif),
+        (IfdKind::Primary, 0x8825, IfdKind::Gps),
+        (IfdKind::Exif, 0xA005, IfdKind::Interoperability),
+    ];
+    for (parent, tag, kind) in pointers {
+        let offset = ifds.iter()
+            .find(|ifd| ifd.kind() == parent)
+            .and_then(|ifd| ifd.get(tag))
+            .and_then(|entry| entry.value().as_u32());
+        if let Some(offset) = offset {
+            ifds.push(reader.ifd(offset as usize, kind)?.0);
+        }
+    }
+
+    Ok((byte_align, ifds))
+}
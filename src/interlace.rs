@@ -0,0 +1,32 @@
+/// (x offset, y offset, x step, y step) of each Adam7 pass.
+/// https://www.w3.org/TR/png/#8Interlace
+const PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Width and height of the reduced image for each pass. Passes can be empty for small images.
+pub fn pass_dimensions(width: u32, height: u32) -> [(u32, u32); 7] {
+    PASSES.map(|(x, y, dx, dy)| (
+        width.saturating_sub(x).div_ceil(dx),
+        height.saturating_sub(y).div_ceil(dy),
+    ))
+}
+
+/// Copies the pixels of a reduced image into their place in the full image.
+pub fn scatter_pass(image: &mut [u8], pass_pixels: &[u8], pass: usize, width: u32, pass_width: u32, pixel_bytes: usize) {
+    let (x0, y0, dx, dy) = PASSES[pass];
+
+    for (i, pixel) in pass_pixels.chunks(pixel_bytes).enumerate() {
+        let x = x0 as usize + (i % pass_width as usize) * dx as usize;
+        let y = y0 as usize + (i / pass_width as usize) * dy as usize;
+
+        let offset = (y * width as usize + x) * pixel_bytes;
+        image[offset..offset + pixel_bytes].copy_from_slice(pixel);
+    }
+}
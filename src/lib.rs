@@ -1,5 +1,15 @@
 
+pub mod apng;
 pub mod chunks;
+pub mod error;
+pub mod exif;
+mod interlace;
 mod macros;
+pub mod mapped;
+pub mod metadata;
 mod utils;
 pub mod png;
+pub mod reader;
+pub mod rows;
+pub mod stream;
+mod transform;
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::ops::Range;
+
+use memmap2::Mmap;
+
+use crate::error::PngError;
+use crate::png::{ChunkRef, CrcPolicy, PngRef, SIGNATURE};
+
+/// A memory mapped PNG file, chunks are read in place through `as_png_ref`.
+pub struct MappedPng {
+    mmap: Mmap,
+    /// Byte range of every chunk, from its length field to the end of its CRC.
+    chunks: Vec<Range<usize>>,
+}
+
+impl MappedPng {
+    /// Maps the file and checks its structure and CRCs.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be written to or truncated, by this or any other process, for as long as the
+    /// `MappedPng` exists. Changes show up in the mapped bytes after they were validated, and accessing pages
+    /// that were truncated away raises SIGBUS.
+    pub unsafe fn open(path: &str, crc_policy: CrcPolicy) -> Result<MappedPng, PngError> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees the file stays as it is while mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        // Chunks follow each other directly, each with 12 bytes of length, type and CRC around its data
+        let mut offset = SIGNATURE.len();
+        let chunks = PngRef::from_bytes_with_crc_policy(&mmap, crc_policy)?
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let range = offset..offset + 12 + chunk.data.len();
+                offset = range.end;
+                range
+            })
+            .collect();
+
+        Ok(MappedPng { mmap, chunks })
+    }
+
+    /// Borrows the chunks from the mapping without copying or re-checking them.
+    pub fn as_png_ref(&self) -> PngRef<'_> {
+        let chunks = self.chunks.iter()
+            .map(|range| {
+                let bytes = &self.mmap[range.clone()];
+                ChunkRef {
+                    // SAFETY: chunk types were checked to be ASCII letters when opened, and the caller of `open`
+                    // guarantees the file hasn't changed since
+                    chunk_type: unsafe { std::str::from_utf8_unchecked(&bytes[4..8]) },
+                    data: &bytes[8..bytes.len() - 4],
+                    crc: &bytes[bytes.len() - 4..],
+                }
+            })
+            .collect();
+
+        PngRef { chunks }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+}
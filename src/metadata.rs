@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use crate::chunks::{ColorType, iCCP, InterlaceMethod, ParsedChunk, PixelUnit, RenderingIntent};
+use crate::error::PngError;
+use crate::png::PngRef;
+
+/// The chunks metadata is taken from. Everything else, image data especially, is skipped without parsing it.
+const METADATA_CHUNKS: [&str; 11] = ["gAMA", "cHRM", "sRGB", "pHYs", "tIME", "bKGD", "tRNS", "iCCP", "tEXt", "zTXt", "iTXt"];
+
+/// Everything known about an image apart from its pixels, see `PNG::metadata`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+    /// Image gamma from gAMA, e.g. 0.45455 for a display gamma of 2.2
+    pub gamma: Option<f64>,
+    pub chromaticities: Option<Chromaticities>,
+    pub srgb_intent: Option<RenderingIntent>,
+    pub physical_size: Option<PhysicalSize>,
+    pub modification_time: Option<ModificationTime>,
+    pub background: Option<Background>,
+    pub transparency: Option<Transparency>,
+    pub icc_profile: Option<iCCP>,
+    /// Text from tEXt, zTXt and iTXt chunks by keyword. If a keyword is used more than once the first entry wins.
+    pub text: BTreeMap<String, String>,
+    /// Why chunks were left out, one error for every chunk of the above that could not be parsed.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub errors: Vec<PngError>,
+}
+
+/// CIE 1931 (x, y) chromaticities of the white point and primaries, from cHRM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chromaticities {
+    pub white: (f64, f64),
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+}
+
+/// Intended pixel size or aspect ratio, from pHYs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhysicalSize {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    /// With `PixelUnit::Unknown` only the aspect ratio is known.
+    pub unit: PixelUnit,
+}
+
+/// Time of the last modification in UTC, from tIME.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModificationTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+/// Background colour from bKGD, samples have the bit depth of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Background {
+    Greyscale(u16),
+    TrueColor(u16, u16, u16),
+    /// Index into PLTE.
+    Indexed(u8),
+}
+
+/// Transparency from tRNS, samples have the bit depth of the image.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transparency {
+    /// Pixels with this grey level are fully transparent.
+    Greyscale(u16),
+    /// Pixels with this colour are fully transparent.
+    TrueColor(u16, u16, u16),
+    /// Alpha for the first entries of PLTE, the remaining entries are opaque.
+    Indexed(Vec<u8>),
+}
+
+impl Metadata {
+    pub(crate) fn from_png(png: &PngRef) -> Result<Metadata, PngError> {
+        let ihdr = png.ihdr()?;
+        let mut metadata = Metadata {
+            width: ihdr.width,
+            height: ihdr.height,
+            bit_depth: ihdr.bit_depth,
+            color_type: ihdr.color_type,
+            interlaced: matches!(ihdr.interlace_method, InterlaceMethod::Adam7),
+            gamma: None,
+            chromaticities: None,
+            srgb_intent: None,
+            physical_size: None,
+            modification_time: None,
+            background: None,
+            transparency: None,
+            icc_profile: None,
+            text: BTreeMap::new(),
+            errors: vec![],
+        };
+
+        // A malformed chunk only takes itself out
+        for chunk in png.chunks.iter().filter(|c| METADATA_CHUNKS.contains(&c.chunk_type)) {
+            let chunk = match ParsedChunk::parse(chunk, Some(ihdr.color_type)) {
+                Ok(chunk) => { chunk }
+                Err(e) => {
+                    metadata.errors.push(e);
+                    continue;
+                }
+            };
+
+            match chunk {
+                ParsedChunk::gAMA(gama) => { metadata.gamma = Some(gama.gamma()) }
+                ParsedChunk::cHRM(chrm) => {
+                    metadata.chromaticities = Some(Chromaticities {
+                        white: chrm.white_point(),
+                        red: chrm.red(),
+                        green: chrm.green(),
+                        blue: chrm.blue(),
+                    });
+                }
+                ParsedChunk::sRGB(srgb) => { metadata.srgb_intent = Some(srgb.intent()) }
+                ParsedChunk::pHYs(phys) => {
+                    metadata.physical_size = Some(PhysicalSize {
+                        pixels_per_unit_x: phys.pixels_per_unit_x(),
+                        pixels_per_unit_y: phys.pixels_per_unit_y(),
+                        unit: phys.unit(),
+                    });
+                }
+                ParsedChunk::tIME(time) => {
+                    metadata.modification_time = Some(ModificationTime {
+                        year: time.year(),
+                        month: time.month(),
+                        day: time.day(),
+                        hour: time.hour(),
+                        minute: time.minute(),
+                        second: time.second(),
+                    });
+                }
+                ParsedChunk::bKGD_Greyscale(bkgd) => { metadata.background = Some(Background::Greyscale(bkgd.value())) }
+                ParsedChunk::bKGD_TrueColor(bkgd) => {
+                    metadata.background = Some(Background::TrueColor(bkgd.red(), bkgd.green(), bkgd.blue()));
+                }
+                ParsedChunk::bKGD_Indexed(bkgd) => { metadata.background = Some(Background::Indexed(bkgd.index())) }
+                ParsedChunk::tRNS_Greyscale(trns) => { metadata.transparency = Some(Transparency::Greyscale(trns.value())) }
+                ParsedChunk::tRNS_TrueColor(trns) => {
+                    metadata.transparency = Some(Transparency::TrueColor(trns.red(), trns.green(), trns.blue()));
+                }
+                ParsedChunk::tRNS_Indexed(trns) => { metadata.transparency = Some(Transparency::Indexed(trns.values().to_vec())) }
+                ParsedChunk::iCCP(iccp) => { metadata.icc_profile = Some(iccp) }
+                ParsedChunk::tEXt(text) => { metadata.add_text(text.keyword(), text.text()) }
+                ParsedChunk::zTXt(text) => { metadata.add_text(text.keyword(), text.text()) }
+                ParsedChunk::iTXt(text) => { metadata.add_text(text.keyword(), text.text()) }
+                _ => {}
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn add_text(&mut self, keyword: &str, text: &str) {
+        self.text.entry(String::from(keyword)).or_insert_with(|| String::from(text));
+    }
+}
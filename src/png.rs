@@ -3,17 +3,35 @@ use std::fmt::{Debug, Display, Formatter};
 use std::fs;
 use std::io::Read;
 
-use compress::zlib;
 use rayon::prelude::*;
 
-use crate::chunks::{FilterType, FromChunk, IHDR};
-use crate::utils;
+use crate::chunks::{ColorType, ParsedChunk, FilterType, eXIf, gAMA, iCCP, InterlaceMethod, TryFromChunk, IHDR, PLTE, tRNS_Greyscale, tRNS_Indexed, tRNS_TrueColor};
+use crate::apng::Frames;
+use crate::error::PngError;
+use crate::mapped::MappedPng;
+use crate::metadata::Metadata;
+use crate::reader::ChunkReader;
+use crate::rows::Rows;
+use crate::utils::Inflater;
+use crate::{interlace, transform, utils};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CrcPolicy {
+    /// Fail with `PngError::BadCrc`.
+    #[default]
+    Error,
+    /// Keep the chunk, mismatches can be listed with `PNG::crc_errors`.
+    Warn,
+    /// Don't check the CRC at all.
+    Ignore,
+}
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chunk {
-    length: u32,
+    pub(crate) length: u32,
     pub chunk_type: String,
     pub data: Vec<u8>,
-    crc: Vec<u8>,
+    pub(crate) crc: Vec<u8>,
 }
 
 impl Display for Chunk {
@@ -29,27 +47,144 @@ impl Debug for Chunk {
 }
 
 impl Chunk {
-    pub fn from_byte_stream(stream: &mut &[u8]) -> Chunk {
+    pub fn from_byte_stream(stream: &mut &[u8], crc_policy: CrcPolicy) -> Result<Chunk, PngError> {
+        Ok(ChunkRef::from_byte_stream(stream, crc_policy)?.to_owned())
+    }
+
+    pub(crate) fn parse_chunk_type(bytes: &[u8]) -> Result<&str, PngError> {
+        if !bytes.iter().all(|x| x.is_ascii_alphabetic()) {
+            return Err(PngError::invalid_chunk(
+                &String::from_utf8_lossy(bytes),
+                "Chunk type must consist of ASCII letters",
+            ));
+        }
+
+        Ok(std::str::from_utf8(bytes).unwrap())
+    }
+
+    /// Borrows the chunk without copying its data.
+    pub fn as_chunk_ref(&self) -> ChunkRef<'_> {
+        ChunkRef {
+            chunk_type: &self.chunk_type,
+            data: &self.data,
+            crc: &self.crc,
+        }
+    }
+
+    /// Length of the chunk data as stored in the file, even if the data was skipped.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// The CRC stored in the file.
+    pub fn stored_crc(&self) -> u32 {
+        self.as_chunk_ref().stored_crc()
+    }
+
+    /// The CRC of the chunk type and data.
+    pub fn computed_crc(&self) -> u32 {
+        self.as_chunk_ref().computed_crc()
+    }
+
+    pub fn crc_ok(&self) -> bool {
+        self.as_chunk_ref().crc_ok()
+    }
+
+    pub fn check_crc(&self, crc_policy: CrcPolicy) -> Result<(), PngError> {
+        self.as_chunk_ref().check_crc(crc_policy)
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.as_chunk_ref().is_critical()
+    }
+}
+
+/// A chunk borrowed from a source buffer.
+#[derive(Clone, Copy)]
+pub struct ChunkRef<'a> {
+    pub chunk_type: &'a str,
+    pub data: &'a [u8],
+    pub(crate) crc: &'a [u8],
+}
+
+impl Display for ChunkRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chunk (type: {}, length: {}, critical: {})", self.chunk_type, self.data.len(), self.is_critical())
+    }
+}
+
+impl Debug for ChunkRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn from_byte_stream(stream: &mut &'a [u8], crc_policy: CrcPolicy) -> Result<ChunkRef<'a>, PngError> {
         // Chunk length
-        let chunk_length = utils::read_be_u32_mut(stream);
+        let chunk_length = utils::read_be_u32_mut(stream)?;
 
         // Chunk type
-        let chunk_type = utils::read_str_mut(stream, 4);
+        let chunk_type = Chunk::parse_chunk_type(utils::read_bytes_mut(stream, 4)?)?;
 
         // Data
-        let (data, rest) = stream.split_at(chunk_length as usize);
-        *stream = rest;
+        let data = utils::read_bytes_mut(stream, chunk_length as usize)?;
 
         // CRC
-        let (crc, rest) = stream.split_at(4);
-        *stream = rest;
+        let crc = utils::read_bytes_mut(stream, 4)?;
 
-        Chunk {
-            length: chunk_length,
+        let chunk = ChunkRef {
             chunk_type,
-            data: Vec::from(data),
-            crc: Vec::from(crc),
+            data,
+            crc,
+        };
+        chunk.check_crc(crc_policy)?;
+
+        Ok(chunk)
+    }
+
+    /// Copies the chunk out of the source buffer.
+    pub fn to_owned(&self) -> Chunk {
+        Chunk {
+            length: self.data.len() as u32,
+            chunk_type: String::from(self.chunk_type),
+            data: Vec::from(self.data),
+            crc: Vec::from(self.crc),
+        }
+    }
+
+    /// The CRC stored in the file. Chunks cut off before their CRC (see `PngRef::from_bytes_lenient`) have 0.
+    pub fn stored_crc(&self) -> u32 {
+        if self.crc.len() < 4 { 0 } else { utils::read_be_u32(self.crc) }
+    }
+
+    /// The CRC of the chunk type and data.
+    pub fn computed_crc(&self) -> u32 {
+        utils::crc32(&[self.chunk_type.as_bytes(), self.data])
+    }
+
+    pub fn crc_ok(&self) -> bool {
+        self.crc.len() == 4 && self.stored_crc() == self.computed_crc()
+    }
+
+    pub fn check_crc(&self, crc_policy: CrcPolicy) -> Result<(), PngError> {
+        match crc_policy {
+            CrcPolicy::Error => { self.crc_error().map_or(Ok(()), Err) }
+            CrcPolicy::Warn | CrcPolicy::Ignore => { Ok(()) }
+        }
+    }
+
+    /// `PngError::BadCrc` if the stored CRC does not match.
+    pub(crate) fn crc_error(&self) -> Option<PngError> {
+        if self.crc_ok() {
+            return None;
         }
+
+        Some(PngError::BadCrc {
+            chunk_type: String::from(self.chunk_type),
+            stored: self.stored_crc(),
+            computed: self.computed_crc(),
+        })
     }
 
     pub fn is_critical(&self) -> bool {
@@ -58,32 +193,158 @@ impl Chunk {
 }
 
 
+pub(crate) const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PNG {
     pub chunks: Vec<Chunk>,
 }
 
+/// A PNG whose chunks borrow from a source buffer, such as a `&[u8]` or memory map.
+#[derive(Debug)]
+pub struct PngRef<'a> {
+    pub chunks: Vec<ChunkRef<'a>>,
+}
 
-impl PNG {
-    pub fn open(path: &str) -> PNG {
-        let contents = fs::read(path).expect("Error opening file.");
-        let header = &contents[..8];
+/// Unfiltered pixel data. Samples below 8 bits are unpacked to one byte each (not rescaled),
+/// 16 bit samples are big-endian byte pairs.
+#[derive(Debug)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    pub data: Vec<u8>,
+}
 
-        if header != vec![137, 80, 78, 71, 13, 10, 26, 10] {
-            panic!("Invalid PNG header: {:?}", header.iter().map(|x| format!("{x:X}")).collect::<Vec<String>>())
-        }
+/// What `PNG::decode_lenient` was able to recover from damaged image data.
+#[derive(Debug)]
+pub struct RecoveryReport {
+    /// Rows decoded from the image data, all rows below them hold the fill colour.
+    pub rows_recovered: u32,
+    /// Everything that went wrong, in the order it was found.
+    pub errors: Vec<PngError>,
+}
 
-        // Generate chunks
-        let mut body = &contents[8..];
-        let mut chunks = Vec::new();
-        while !body.is_empty() {
-            chunks.push(Chunk::from_byte_stream(&mut body));
+impl RecoveryReport {
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Transformations applied by `PNG::decode_with`.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Look up the pixels of indexed color images in PLTE, giving RGB8, or RGBA8 if there is a tRNS chunk.
+    pub expand_palette: bool,
+    /// Re-encode colour samples using the transfer function from sRGB or gAMA. Images with neither are left
+    /// unchanged. Indexed color images must also set `expand_palette`.
+    pub gamma_correction: Option<GammaCorrection>,
+    /// Rotate and flip the image upright using the Orientation tag of eXIf, swapping width and height for the
+    /// orientations that turn it sideways. Images without the tag, or with an eXIf that can't be parsed, are left
+    /// unchanged.
+    pub apply_orientation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GammaCorrection {
+    /// Encode for a display with the given gamma, e.g. 2.2. Must be finite and positive.
+    Display(f64),
+    /// Convert to linear light.
+    Linear,
+}
+
+/// Byte order of the values returned by `PNG::decode_u16_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Endianness {
+    #[default]
+    Native,
+    /// Bytes are passed through in file order, so values are only correct on big-endian machines.
+    Big,
+}
+
+/// 8 bit pixel layouts that any image can be converted to with `PNG::decode_as`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelFormat {
+    Rgba8,
+    Rgb8,
+    Luma8,
+    LumaA8,
+}
+
+impl PixelFormat {
+    pub fn color_type(&self) -> ColorType {
+        match self {
+            PixelFormat::Rgba8 => { ColorType::TrueColorAlpha }
+            PixelFormat::Rgb8 => { ColorType::TrueColor }
+            PixelFormat::Luma8 => { ColorType::Greyscale }
+            PixelFormat::LumaA8 => { ColorType::GreyscaleAlpha }
         }
+    }
+}
 
 
-        PNG {
-            chunks
+impl PNG {
+    pub fn open(path: &str) -> Result<PNG, PngError> {
+        PNG::open_with_crc_policy(path, CrcPolicy::default())
+    }
+
+    pub fn open_with_crc_policy(path: &str, crc_policy: CrcPolicy) -> Result<PNG, PngError> {
+        PNG::from_bytes_with_crc_policy(&fs::read(path)?, crc_policy)
+    }
+
+    /// Maps the file into memory instead of reading it, see `MappedPng`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, see `MappedPng::open`.
+    pub unsafe fn open_mmap(path: &str) -> Result<MappedPng, PngError> {
+        unsafe { MappedPng::open(path, CrcPolicy::default()) }
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<PNG, PngError> {
+        PNG::from_reader_with_crc_policy(reader, CrcPolicy::default())
+    }
+
+    /// Reads chunks up to IEND without buffering the whole stream first.
+    pub fn from_reader_with_crc_policy(reader: impl Read, crc_policy: CrcPolicy) -> Result<PNG, PngError> {
+        let mut chunk_reader = ChunkReader::new(reader)?;
+        chunk_reader.set_crc_policy(crc_policy);
+
+        Ok(PNG {
+            chunks: chunk_reader.collect::<Result<Vec<_>, _>>()?
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<PNG, PngError> {
+        PNG::from_bytes_with_crc_policy(bytes, CrcPolicy::default())
+    }
+
+    pub fn from_bytes_with_crc_policy(bytes: &[u8], crc_policy: CrcPolicy) -> Result<PNG, PngError> {
+        Ok(PngRef::from_bytes_with_crc_policy(bytes, crc_policy)?.to_owned())
+    }
+
+    /// Parses as many chunks as possible from a damaged file and returns the recoverable errors alongside them,
+    /// see `PngRef::from_bytes_lenient`. The chunks are copied out of `bytes`.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<(PNG, Vec<PngError>), PngError> {
+        let (png_ref, errors) = PngRef::from_bytes_lenient(bytes)?;
+        Ok((png_ref.to_owned(), errors))
+    }
+
+    pub fn as_png_ref(&self) -> PngRef<'_> {
+        PngRef {
+            chunks: self.chunks.iter().map(|c| c.as_chunk_ref()).collect()
+        }
+    }
+
+    pub(crate) fn check_signature(bytes: &[u8]) -> Result<(), PngError> {
+        let header = &bytes[..min(SIGNATURE.len(), bytes.len())];
+        if header != SIGNATURE {
+            return Err(PngError::BadSignature(header.to_vec()));
         }
+
+        Ok(())
     }
 
     /// https://www.w3.org/TR/PNG-Filters.html
@@ -107,9 +368,11 @@ impl PNG {
     fn apply_filter_paeth(x: u8, l: u8, u: u8, ul: u8) -> u8 { x.wrapping_add(PNG::paeth(l, u, ul)) }
 
 
-    /// Applies a filter to a single scanline.
-    fn apply_filter_scanlines(current_scanline: &[u8], prior_scanline: &[u8]) -> Vec<u8> {
-        let filter_type = FilterType::try_from(current_scanline[0]).expect("Invalid filter type");
+    /// Applies a filter to a single scanline. `prior_scanline` must already be unfiltered and `stride` is the
+    /// distance in bytes to the corresponding byte of the previous pixel.
+    pub(crate) fn apply_filter_scanlines(current_scanline: &[u8], prior_scanline: &[u8], stride: usize) -> Result<Vec<u8>, PngError> {
+        let filter_type = FilterType::try_from(current_scanline[0])
+            .map_err(|_| PngError::invalid_chunk("IDAT", format!("Invalid filter type: {}", current_scanline[0])))?;
 
         // Select filter function f(x, l, u, ul) -> y
         let filter_func = match filter_type {
@@ -120,66 +383,555 @@ impl PNG {
             FilterType::Paeth => { PNG::apply_filter_paeth }
         };
 
-        // Apply filter
-        (1..current_scanline.len())
-            .map(|i| filter_func(
-                current_scanline[i], // x
-                if i == 1 { 0 } else { current_scanline[i - 1] }, // l
+        // Apply filter, each byte depends on the already unfiltered bytes to its left
+        let filtered = &current_scanline[1..];
+        let mut unfiltered = Vec::with_capacity(filtered.len());
+        for i in 0..filtered.len() {
+            let y = filter_func(
+                filtered[i], // x
+                if i < stride { 0 } else { unfiltered[i - stride] }, // l
                 prior_scanline[i], // u
-                if i == 1 { 0 } else { prior_scanline[i - 1] }, // ul
-            ))
-            .collect()
+                if i < stride { 0 } else { prior_scanline[i - stride] }, // ul
+            );
+            unfiltered.push(y);
+        }
+
+        Ok(unfiltered)
     }
 
-    /// Applies a filter to all scanlines.
-    fn filter(bytes: &[u8], ihdr: &IHDR) -> Vec<u8> {
-        let scanline_length = ((ihdr.width * ihdr.color_type.bytes_per_pixel()) + 1) as usize;
-        let scanlines = bytes
-            .chunks(scanline_length)
-            .collect::<Vec<_>>();
+    /// Applies a filter to all scanlines of an image with the given dimensions.
+    fn filter(bytes: &[u8], ihdr: &IHDR, width: u32, height: u32) -> Result<Vec<u8>, PngError> {
+        let scanline_length = ihdr.scanline_length_for_width(width)?;
+        let filtered_length = ihdr.filtered_length(width, height)?;
+        if bytes.len() < filtered_length {
+            return Err(PngError::Truncated);
+        }
 
         // Add dummy scanline as filters require prior scanline
-        let dummy_scanline = vec![0_u8; scanline_length];
+        let mut prior_scanline = vec![0_u8; scanline_length - 1];
+
+        // Scanlines depend on the unfiltered scanline above, so must be done in order
+        let mut filtered = Vec::with_capacity(filtered_length - height as usize);
+        for scanline in bytes.chunks(scanline_length).take(height as usize) {
+            prior_scanline = PNG::apply_filter_scanlines(scanline, &prior_scanline, ihdr.filter_stride())?;
+            filtered.extend(&prior_scanline);
+        }
+
+
+        Ok(filtered)
+    }
+
+
+    /// Unpacks sub-byte samples into one byte each, dropping the padding bits at the end of each row.
+    pub(crate) fn unpack_samples(rows: &[u8], ihdr: &IHDR, width: u32) -> Vec<u8> {
+        let bit_depth = ihdr.bit_depth as usize;
+        let samples_per_row = width as usize * ihdr.color_type.samples_per_pixel() as usize;
+        let mask = (1_u8 << bit_depth) - 1;
+
+        // Fits as the scanline length was checked before unfiltering
+        rows.chunks((samples_per_row * bit_depth).div_ceil(8))
+            .flat_map(|row| (0..samples_per_row).map(move |i| {
+                let bit = i * bit_depth;
+                (row[bit / 8] >> (8 - bit_depth - bit % 8)) & mask
+            }))
+            .collect()
+    }
+
+    /// Unfilters and unpacks the scanlines of a single (possibly reduced) image.
+    fn decode_pass(bytes: &[u8], ihdr: &IHDR, width: u32, height: u32) -> Result<Vec<u8>, PngError> {
+        let filtered = PNG::filter(bytes, ihdr, width, height)?;
 
+        if ihdr.bit_depth < 8 {
+            Ok(PNG::unpack_samples(&filtered, ihdr, width))
+        } else {
+            Ok(filtered)
+        }
+    }
+
+    /// Decodes the 7 reduced images of an Adam7 interlaced image and places them into a full size image.
+    pub(crate) fn decode_adam7(bytes: &[u8], ihdr: &IHDR) -> Result<Vec<u8>, PngError> {
+        // Check all passes are there before allocating the full image, the dimensions may be far larger than the
+        // data
+        if bytes.len() < ihdr.data_length()? {
+            return Err(PngError::Truncated);
+        }
+
+        let pixel_bytes = ihdr.unpacked_bytes_per_pixel();
+        let mut image = vec![0_u8; ihdr.image_length(ihdr.width, ihdr.height, pixel_bytes)?];
 
-        let row_indices = (0..scanlines.len()).collect::<Vec<usize>>();
-        let filtered = row_indices
-            .iter()
-            // .par_iter()
-            .map(|&i| {
-                let prior_scanline = if i == 0 { &dummy_scanline } else { scanlines[i - 1] };
-                PNG::apply_filter_scanlines(scanlines[i], prior_scanline)
-            })
-            .flatten()
-            .collect();
+        let mut offset = 0;
+        for (pass, (width, height)) in interlace::pass_dimensions(ihdr.width, ihdr.height).into_iter().enumerate() {
+            // Empty passes don't have any scanlines, not even filter type bytes
+            if width == 0 || height == 0 {
+                continue;
+            }
 
+            let pass_bytes = bytes.get(offset..).ok_or(PngError::Truncated)?;
+            let pass_pixels = PNG::decode_pass(pass_bytes, ihdr, width, height)?;
+            interlace::scatter_pass(&mut image, &pass_pixels, pass, ihdr.width, width, pixel_bytes);
 
-        filtered
+            offset += ihdr.filtered_length(width, height)?;
+        }
+
+        Ok(image)
     }
 
+    /// Decompresses and unfilters the image data of an image or APNG frame.
+    pub(crate) fn decode_image_data(compressed: &[u8], ihdr: &IHDR) -> Result<Vec<u8>, PngError> {
+        // Decompress, anything past the length of the scanlines is an error
+        let decompressed = utils::inflate(compressed, ihdr.data_length()?)?;
 
-    pub fn get_image_data(&self) -> Vec<u8> {
+
+        // Filter
+        match ihdr.interlace_method {
+            InterlaceMethod::None => { PNG::decode_pass(&decompressed, ihdr, ihdr.width, ihdr.height) }
+            InterlaceMethod::Adam7 => { PNG::decode_adam7(&decompressed, ihdr) }
+        }
+    }
+
+    pub fn find_chunk(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunks.iter().find(|c| c.chunk_type == chunk_type)
+    }
+
+    /// A `PngError::BadCrc` for every chunk whose stored CRC does not match, for flagging damaged files that
+    /// were read with `CrcPolicy::Warn`.
+    pub fn crc_errors(&self) -> Vec<PngError> {
+        self.as_png_ref().crc_errors()
+    }
+
+    pub fn ihdr(&self) -> Result<IHDR, PngError> {
+        self.as_png_ref().ihdr()
+    }
+
+    /// Parses every chunk into the struct for its type, in file order.
+    pub fn parsed_chunks(&self) -> Result<Vec<ParsedChunk>, PngError> {
+        self.as_png_ref().parsed_chunks()
+    }
+
+    /// Collects the header and all ancillary chunks into one struct. Only a missing or invalid IHDR is an error,
+    /// other chunks that can't be parsed are left out and listed in `Metadata::errors`.
+    pub fn metadata(&self) -> Result<Metadata, PngError> {
+        self.as_png_ref().metadata()
+    }
+
+    pub fn icc_profile(&self) -> Result<Option<iCCP>, PngError> {
+        self.as_png_ref().icc_profile()
+    }
+
+    pub fn get_image_data(&self) -> Result<Vec<u8>, PngError> {
+        self.as_png_ref().get_image_data()
+    }
+
+    pub fn decode(&self) -> Result<DecodedImage, PngError> {
+        self.as_png_ref().decode()
+    }
+
+    pub fn decode_lenient(&self, fill_colour: &[u8]) -> Result<(DecodedImage, RecoveryReport), PngError> {
+        self.as_png_ref().decode_lenient(fill_colour)
+    }
+
+    /// Decodes the frames of an animated PNG, composited into RGBA8 canvases. A PNG without acTL has a single
+    /// frame showing the default image.
+    pub fn frames(&self) -> Result<Frames<'_>, PngError> {
+        self.as_png_ref().frames()
+    }
+
+    /// Decodes one row at a time, keeping only the current and previous scanline in memory.
+    pub fn rows(&self) -> Result<Rows<'_>, PngError> {
+        self.as_png_ref().rows()
+    }
+
+    pub fn decode_with(&self, options: &DecodeOptions) -> Result<DecodedImage, PngError> {
+        self.as_png_ref().decode_with(options)
+    }
+
+    /// Decodes into a fixed pixel format regardless of how the image is stored. Palettes and tRNS colour keys
+    /// are applied, samples are scaled to 8 bits, and colour is converted to luma (or back) as needed.
+    pub fn decode_as(&self, format: PixelFormat) -> Result<DecodedImage, PngError> {
+        self.as_png_ref().decode_as(format)
+    }
+
+    /// Decodes a 16 bit image into one `u16` per sample, in native byte order.
+    pub fn decode_u16(&self) -> Result<Vec<u16>, PngError> {
+        self.as_png_ref().decode_u16()
+    }
+
+    pub fn decode_u16_with(&self, endianness: Endianness) -> Result<Vec<u16>, PngError> {
+        self.as_png_ref().decode_u16_with(endianness)
+    }
+}
+
+impl<'a> PngRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<PngRef<'a>, PngError> {
+        PngRef::from_bytes_with_crc_policy(bytes, CrcPolicy::default())
+    }
+
+    /// Reads chunks up to IEND, anything after it is ignored like in `PNG::from_reader`.
+    pub fn from_bytes_with_crc_policy(bytes: &'a [u8], crc_policy: CrcPolicy) -> Result<PngRef<'a>, PngError> {
+        PNG::check_signature(bytes)?;
+
+        // Generate chunks
+        let mut body = &bytes[SIGNATURE.len()..];
+        let mut chunks = Vec::new();
+        while !body.is_empty() {
+            let chunk = ChunkRef::from_byte_stream(&mut body, crc_policy)?;
+            chunks.push(chunk);
+
+            if chunk.chunk_type == "IEND" {
+                break;
+            }
+        }
+
+
+        Ok(PngRef {
+            chunks
+        })
+    }
+
+    /// Reads as many chunks as possible from a damaged file, stopping after IEND. CRC mismatches are reported
+    /// instead of failing, and a chunk cut off by the end of the file is kept with whatever data is present.
+    /// Only a bad signature is an error.
+    pub fn from_bytes_lenient(bytes: &'a [u8]) -> Result<(PngRef<'a>, Vec<PngError>), PngError> {
+        PNG::check_signature(bytes)?;
+
+        let mut body = &bytes[SIGNATURE.len()..];
+        let mut chunks = Vec::new();
+        let mut errors = Vec::new();
+        while !body.is_empty() {
+            let mut stream = body;
+            match ChunkRef::from_byte_stream(&mut stream, CrcPolicy::Ignore) {
+                Ok(chunk) => {
+                    errors.extend(chunk.crc_error());
+                    body = stream;
+                    chunks.push(chunk);
+
+                    if chunk.chunk_type == "IEND" {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    // Keep the remains of a truncated chunk, as long as its type is intact
+                    if body.len() >= 8 {
+                        if let Ok(chunk_type) = Chunk::parse_chunk_type(&body[4..8]) {
+                            let length = utils::read_be_u32(&body[..4]) as usize;
+                            chunks.push(ChunkRef {
+                                chunk_type,
+                                data: &body[8..min(8 + length, body.len())],
+                                crc: &[],
+                            });
+                        }
+                    }
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+
+        Ok((PngRef { chunks }, errors))
+    }
+
+    /// Copies every chunk out of the source buffer.
+    pub fn to_owned(&self) -> PNG {
+        PNG {
+            chunks: self.chunks.iter().map(|c| c.to_owned()).collect()
+        }
+    }
+
+    pub fn find_chunk(&self, chunk_type: &str) -> Option<&ChunkRef<'a>> {
+        self.chunks.iter().find(|c| c.chunk_type == chunk_type)
+    }
+
+    pub fn crc_errors(&self) -> Vec<PngError> {
+        self.chunks.iter().filter_map(|c| c.crc_error()).collect()
+    }
+
+    pub fn ihdr(&self) -> Result<IHDR, PngError> {
+        match self.chunks.first() {
+            Some(chunk) if chunk.chunk_type == "IHDR" => { IHDR::try_from_chunk_ref(chunk) }
+            _ => { Err(PngError::MissingChunk(String::from("IHDR"))) }
+        }
+    }
+
+    pub fn parsed_chunks(&self) -> Result<Vec<ParsedChunk>, PngError> {
+        let mut color_type = None;
+        let mut parsed_chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in &self.chunks {
+            let parsed = ParsedChunk::parse(chunk, color_type)?;
+            if let ParsedChunk::IHDR(ihdr) = &parsed {
+                color_type = Some(ihdr.color_type);
+            }
+            parsed_chunks.push(parsed);
+        }
+
+        Ok(parsed_chunks)
+    }
+
+    pub fn metadata(&self) -> Result<Metadata, PngError> {
+        Metadata::from_png(self)
+    }
+
+    pub fn icc_profile(&self) -> Result<Option<iCCP>, PngError> {
+        self.find_chunk("iCCP")
+            .map(iCCP::try_from_chunk_ref)
+            .transpose()
+    }
+
+    pub fn get_image_data(&self) -> Result<Vec<u8>, PngError> {
+        Ok(self.decode()?.data)
+    }
+
+    pub fn decode(&self) -> Result<DecodedImage, PngError> {
         // Get IDHR chunk
-        let ihdr = IHDR::from_chunk(&self.chunks[0]);
+        let ihdr = self.ihdr()?;
 
         // Filter chunks
         let data_chunks = self.chunks.iter()
             .filter(|c| c.chunk_type == "IDAT")
-            .collect::<Vec<&Chunk>>();
+            .collect::<Vec<_>>();
+        if data_chunks.is_empty() {
+            return Err(PngError::MissingChunk(String::from("IDAT")));
+        }
 
         // Concatenate chunk data
         let mut all_chunks: Vec<u8> = vec![];
         for chunk in data_chunks {
-            all_chunks.extend(&chunk.data)
+            all_chunks.extend(chunk.data)
         }
 
-        // Decompress
-        let mut decompressed = vec![];
-        zlib::Decoder::new(all_chunks.as_slice()).read_to_end(&mut decompressed)
-            .expect("Failed to decompress byte stream");
+        let data = PNG::decode_image_data(&all_chunks, &ihdr)?;
 
+        Ok(DecodedImage {
+            width: ihdr.width,
+            height: ihdr.height,
+            color_type: ihdr.color_type,
+            bit_depth: ihdr.bit_depth,
+            data,
+        })
+    }
+
+    /// Decodes every complete row it can and fills the rest of the image with `fill_colour`, which must be a
+    /// single pixel in the layout of `decode`. Problems with the image data are collected in the report instead
+    /// of failing, only a missing or invalid IHDR is an error. So is an IHDR with a bad CRC, as its dimensions can't
+    /// be trusted to size the image. Interlaced images are recovered only when all of
+    /// their image data is intact, as each pass covers the whole image.
+    pub fn decode_lenient(&self, fill_colour: &[u8]) -> Result<(DecodedImage, RecoveryReport), PngError> {
+        let ihdr = self.ihdr()?;
+        let pixel_bytes = ihdr.unpacked_bytes_per_pixel();
+        if fill_colour.len() != pixel_bytes {
+            return Err(PngError::InvalidArgument(format!(
+                "Fill colour has {} bytes, pixels of this image have {}", fill_colour.len(), pixel_bytes
+            )));
+        }
+
+        if let Some(e) = self.find_chunk("IHDR").and_then(|c| c.crc_error()) {
+            return Err(e);
+        }
+
+        let mut report = RecoveryReport {
+            rows_recovered: 0,
+            errors: vec![],
+        };
+
+        // Concatenate chunk data
+        let mut all_chunks: Vec<u8> = vec![];
+        for chunk in self.chunks.iter().filter(|c| c.chunk_type == "IDAT") {
+            all_chunks.extend(chunk.data)
+        }
+        if all_chunks.is_empty() {
+            report.errors.push(PngError::MissingChunk(String::from("IDAT")));
+        }
+
+        // Decompress as much as possible
+        let mut decompressed = vec![];
+        let mut inflater = Inflater::new(ihdr.data_length()?);
+        match inflater.inflate(&mut all_chunks.as_slice(), &mut decompressed, usize::MAX) {
+            Ok(true) => {}
+            Ok(false) => { report.errors.push(PngError::Truncated) }
+            Err(e) => { report.errors.push(e) }
+        }
 
         // Filter
-        PNG::filter(&decompressed, &ihdr)
+        let image_length = ihdr.image_length(ihdr.width, ihdr.height, pixel_bytes)?;
+        let scanline_length = ihdr.scanline_length()?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(image_length).map_err(|_| ihdr.too_large())?;
+        match ihdr.interlace_method {
+            InterlaceMethod::None => {
+                let mut prior_scanline = vec![0_u8; scanline_length - 1];
+                for scanline in decompressed.chunks_exact(scanline_length).take(ihdr.height as usize) {
+                    match PNG::apply_filter_scanlines(scanline, &prior_scanline, ihdr.filter_stride()) {
+                        Ok(unfiltered) => { prior_scanline = unfiltered }
+                        Err(e) => {
+                            report.errors.push(e);
+                            break;
+                        }
+                    }
+
+                    if ihdr.bit_depth < 8 {
+                        data.extend(PNG::unpack_samples(&prior_scanline, &ihdr, ihdr.width));
+                    } else {
+                        data.extend(&prior_scanline);
+                    }
+                    report.rows_recovered += 1;
+                }
+            }
+            InterlaceMethod::Adam7 => {
+                match PNG::decode_adam7(&decompressed, &ihdr) {
+                    Ok(image) => {
+                        data = image;
+                        report.rows_recovered = ihdr.height;
+                    }
+                    Err(e) => { report.errors.push(e) }
+                }
+            }
+        }
+
+        // A complete zlib stream can still hold too few scanlines
+        if report.rows_recovered < ihdr.height && report.errors.is_empty() {
+            report.errors.push(PngError::Truncated);
+        }
+
+        // Fill missing rows
+        while data.len() < image_length {
+            data.extend(fill_colour);
+        }
+
+        let image = DecodedImage {
+            width: ihdr.width,
+            height: ihdr.height,
+            color_type: ihdr.color_type,
+            bit_depth: ihdr.bit_depth,
+            data,
+        };
+
+        Ok((image, report))
+    }
+
+    pub fn frames(&self) -> Result<Frames<'a>, PngError> {
+        Frames::new(self)
+    }
+
+    pub fn rows(&self) -> Result<Rows<'a>, PngError> {
+        let ihdr = self.ihdr()?;
+
+        let data_chunks = self.chunks.iter()
+            .filter(|c| c.chunk_type == "IDAT")
+            .map(|c| c.data)
+            .collect::<Vec<_>>();
+        if data_chunks.is_empty() {
+            return Err(PngError::MissingChunk(String::from("IDAT")));
+        }
+
+        Rows::new(ihdr, data_chunks)
+    }
+
+    pub fn decode_with(&self, options: &DecodeOptions) -> Result<DecodedImage, PngError> {
+        if let Some(GammaCorrection::Display(gamma)) = options.gamma_correction {
+            if !gamma.is_finite() || gamma <= 0. {
+                return Err(PngError::InvalidArgument(format!("Display gamma must be positive, got {}", gamma)));
+            }
+        }
+
+        let mut image = self.decode()?;
+
+        if options.expand_palette && image.color_type == ColorType::IndexedColor {
+            let plte = self.find_chunk("PLTE")
+                .ok_or(PngError::MissingChunk(String::from("PLTE")))?;
+            let plte = PLTE::try_from_chunk_ref(plte)?;
+            let trns = self.find_chunk("tRNS")
+                .map(tRNS_Indexed::try_from_chunk_ref)
+                .transpose()?;
+
+            image = transform::expand_palette(image, &plte, trns.as_ref())?;
+        }
+
+        if let Some(target) = options.gamma_correction {
+            // sRGB takes precedence over gAMA
+            let source = if self.find_chunk("sRGB").is_some() {
+                Some(transform::TransferFunction::Srgb)
+            } else if let Some(chunk) = self.find_chunk("gAMA") {
+                Some(transform::TransferFunction::Gamma(gAMA::try_from_chunk_ref(chunk)?.gamma()))
+            } else {
+                None
+            };
+
+            if let Some(source) = source {
+                image = transform::correct_gamma(image, source, target)?;
+            }
+        }
+
+        if options.apply_orientation {
+            if let Some(chunk) = self.find_chunk("eXIf") {
+                // A malformed eXIf is only metadata, it shouldn't stop the image from decoding
+                let orientation = eXIf::try_from_chunk_ref(chunk)
+                    .ok()
+                    .and_then(|exif| exif.get("Orientation").and_then(|entry| entry.value().as_u32()));
+
+                if let Some(orientation) = orientation {
+                    image = transform::orient(image, orientation);
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// See `PNG::decode_as`.
+    pub fn decode_as(&self, format: PixelFormat) -> Result<DecodedImage, PngError> {
+        let image = self.decode_with(&DecodeOptions { expand_palette: true, ..Default::default() })?;
+        let color_key = self.color_key(image.color_type)?;
+
+        Ok(transform::convert(image, color_key.as_deref(), format))
+    }
+
+    /// Samples of the pixels which should be fully transparent, from tRNS.
+    pub(crate) fn color_key(&self, color_type: ColorType) -> Result<Option<Vec<u16>>, PngError> {
+        match (color_type, self.find_chunk("tRNS")) {
+            (ColorType::Greyscale, Some(chunk)) => {
+                Ok(Some(vec![tRNS_Greyscale::try_from_chunk_ref(chunk)?.value()]))
+            }
+            (ColorType::TrueColor, Some(chunk)) => {
+                let trns = tRNS_TrueColor::try_from_chunk_ref(chunk)?;
+                Ok(Some(vec![trns.red(), trns.green(), trns.blue()]))
+            }
+            _ => { Ok(None) }
+        }
+    }
+
+    pub fn decode_u16(&self) -> Result<Vec<u16>, PngError> {
+        self.decode_u16_with(Endianness::Native)
+    }
+
+    pub fn decode_u16_with(&self, endianness: Endianness) -> Result<Vec<u16>, PngError> {
+        let image = self.decode()?;
+        if image.bit_depth != 16 {
+            return Err(PngError::UnsupportedFormat(format!("Expected a 16 bit image, got bit depth {}", image.bit_depth)));
+        }
+
+        let from_bytes = match endianness {
+            Endianness::Native => { u16::from_be_bytes }
+            Endianness::Big => { u16::from_ne_bytes }
+        };
+
+        Ok(image.data
+            .chunks_exact(2)
+            .map(|pair| from_bytes([pair[0], pair[1]]))
+            .collect())
+    }
+}
+
+impl TryFrom<&[u8]> for PNG {
+    type Error = PngError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        PNG::from_bytes(bytes)
     }
 }
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = PngError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        PngRef::from_bytes(bytes)
+    }
+}
\ No newline at end of file
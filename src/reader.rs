@@ -0,0 +1,111 @@
+use std::io::{self, Read};
+
+use crate::error::PngError;
+use crate::png::{Chunk, CrcPolicy, PNG, SIGNATURE};
+
+/// Reads chunks one at a time from a stream, stopping after IEND or at the end of the stream.
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    crc_policy: CrcPolicy,
+    skip_data: Box<dyn Fn(&str) -> bool>,
+    finished: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads and checks the PNG signature.
+    pub fn new(mut reader: R) -> Result<ChunkReader<R>, PngError> {
+        let mut signature = vec![];
+        (&mut reader).take(SIGNATURE.len() as u64).read_to_end(&mut signature)?;
+        PNG::check_signature(&signature)?;
+
+        Ok(ChunkReader {
+            reader,
+            crc_policy: CrcPolicy::default(),
+            skip_data: Box::new(|_| false),
+            finished: false,
+        })
+    }
+
+    pub fn set_crc_policy(&mut self, crc_policy: CrcPolicy) {
+        self.crc_policy = crc_policy;
+    }
+
+    /// Chunk types for which `skip` returns true are yielded with empty data, without reading their payload
+    /// into memory. Their CRC is not checked.
+    pub fn set_skip_data(&mut self, skip: impl Fn(&str) -> bool + 'static) {
+        self.skip_data = Box::new(skip);
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), PngError> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => { PngError::Truncated }
+            _ => { PngError::Io(e) }
+        })
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>, PngError> {
+        // Chunk length, a clean end of stream here means there are no more chunks
+        let mut length = [0_u8; 4];
+        let num_read = self.reader.read(&mut length)?;
+        if num_read == 0 {
+            return Ok(None);
+        }
+        self.read_exact(&mut length[num_read..])?;
+        let length = u32::from_be_bytes(length);
+
+        // Chunk type
+        let mut chunk_type = [0_u8; 4];
+        self.read_exact(&mut chunk_type)?;
+        let chunk_type = String::from(Chunk::parse_chunk_type(&chunk_type)?);
+
+        // Data
+        let skip = (self.skip_data)(&chunk_type);
+        let mut data = vec![];
+        if skip {
+            let num_skipped = io::copy(&mut (&mut self.reader).take(length as u64), &mut io::sink())?;
+            if num_skipped < length as u64 {
+                return Err(PngError::Truncated);
+            }
+        } else {
+            (&mut self.reader).take(length as u64).read_to_end(&mut data)?;
+            if data.len() < length as usize {
+                return Err(PngError::Truncated);
+            }
+        }
+
+        // CRC
+        let mut crc = vec![0_u8; 4];
+        self.read_exact(&mut crc)?;
+
+        let chunk = Chunk {
+            length,
+            chunk_type,
+            data,
+            crc,
+        };
+        if !skip {
+            chunk.check_crc(self.crc_policy)?;
+        }
+
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let chunk = self.read_chunk();
+        match &chunk {
+            Ok(Some(c)) if c.chunk_type != "IEND" => {}
+            // Stop after IEND, the end of the stream, or the first error
+            _ => { self.finished = true; }
+        }
+
+        chunk.transpose()
+    }
+}
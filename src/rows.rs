@@ -0,0 +1,84 @@
+use crate::chunks::{InterlaceMethod, IHDR};
+use crate::error::PngError;
+use crate::png::PNG;
+use crate::utils::Inflater;
+
+/// Iterates over the unfiltered rows of a non-interlaced image, inflating IDAT data only as far as needed.
+/// Rows have the same layout as `PNG::decode`, with sub-byte samples unpacked to one byte each.
+pub struct Rows<'a> {
+    ihdr: IHDR,
+    chunks: std::vec::IntoIter<&'a [u8]>,
+    // The rest of the IDAT chunk being inflated
+    current: &'a [u8],
+    inflater: Inflater,
+    // Decompressed data not yet unfiltered
+    scanlines: Vec<u8>,
+    prior_scanline: Vec<u8>,
+    row: u32,
+    finished: bool,
+}
+
+impl<'a> Rows<'a> {
+    pub(crate) fn new(ihdr: IHDR, chunks: Vec<&'a [u8]>) -> Result<Rows<'a>, PngError> {
+        if matches!(ihdr.interlace_method, InterlaceMethod::Adam7) {
+            return Err(PngError::UnsupportedFormat(String::from("Row iteration of interlaced images")));
+        }
+
+        let scanline_length = ihdr.scanline_length()?;
+        Ok(Rows {
+            inflater: Inflater::new(ihdr.data_length()?),
+            ihdr,
+            chunks: chunks.into_iter(),
+            current: &[],
+            scanlines: vec![],
+            // Dummy scanline as filters require prior scanline
+            prior_scanline: vec![0_u8; scanline_length - 1],
+            row: 0,
+            finished: false,
+        })
+    }
+
+    /// Width, height and pixel layout of the rows.
+    pub fn ihdr(&self) -> &IHDR {
+        &self.ihdr
+    }
+
+    fn next_row(&mut self) -> Result<Vec<u8>, PngError> {
+        // Inflate just enough for the next scanline, moving on to the next chunk once the current one is used up
+        let scanline_length = self.prior_scanline.len() + 1;
+        while self.scanlines.len() < scanline_length {
+            if self.inflater.is_finished() {
+                return Err(PngError::Truncated);
+            }
+            if self.current.is_empty() {
+                self.current = self.chunks.next().ok_or(PngError::Truncated)?;
+                continue;
+            }
+            self.inflater.inflate(&mut self.current, &mut self.scanlines, scanline_length)?;
+        }
+
+        let scanline = self.scanlines.drain(..scanline_length).collect::<Vec<_>>();
+        self.prior_scanline = PNG::apply_filter_scanlines(&scanline, &self.prior_scanline, self.ihdr.filter_stride())?;
+
+        if self.ihdr.bit_depth < 8 {
+            Ok(PNG::unpack_samples(&self.prior_scanline, &self.ihdr, self.ihdr.width))
+        } else {
+            Ok(self.prior_scanline.clone())
+        }
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Result<Vec<u8>, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.row >= self.ihdr.height {
+            return None;
+        }
+
+        let row = self.next_row();
+        self.row += 1;
+        self.finished = row.is_err();
+        Some(row)
+    }
+}
@@ -0,0 +1,274 @@
+use std::cmp::min;
+
+use crate::chunks::{InterlaceMethod, TryFromChunk, IHDR};
+use crate::error::PngError;
+use crate::png::{Chunk, ChunkRef, CrcPolicy, PNG, SIGNATURE};
+use crate::utils::{self, Crc32, Inflater};
+
+/// Something that happened while decoding, in the order it occurred in the file.
+#[derive(Debug)]
+pub enum Event {
+    /// The PNG signature was read and is valid.
+    Signature,
+    /// The length and type of a chunk were read, its data may not have arrived yet.
+    ChunkStarted { chunk_type: String, length: u32 },
+    /// The data and CRC of a chunk were read. The data itself is not kept, image data has been passed on as
+    /// rows. `crc_ok` is false if the stored CRC does not match, which is only an error with `CrcPolicy::Error`.
+    ChunkCompleted { chunk_type: String, length: u32, crc_ok: bool },
+    Header(IHDR),
+    /// An unfiltered row, with the same layout as the rows of `PNG::decode`. Rows are emitted as soon as their
+    /// image data arrives, before the CRC of the IDAT chunk holding them has been checked. Rows of interlaced
+    /// images are only available once all image data has arrived.
+    Row { index: u32, data: Vec<u8> },
+    /// The IEND chunk was read, no more events will follow.
+    ImageFinished,
+}
+
+/// The chunk whose data is currently arriving.
+struct PartialChunk {
+    chunk_type: String,
+    length: u32,
+    remaining: usize,
+    crc: Crc32,
+    /// Only kept for IHDR, the data of all other chunks is dropped or inflated as it arrives.
+    data: Vec<u8>,
+}
+
+/// Decodes a PNG from fragments of any size as they arrive, e.g. while downloading.
+///
+/// Image data is inflated and unfiltered as soon as whole scanlines are available, even in the middle of an
+/// IDAT chunk. The data of other chunks is not kept.
+pub struct StreamingDecoder {
+    crc_policy: CrcPolicy,
+    buffer: Vec<u8>,
+    signature_read: bool,
+    chunk: Option<PartialChunk>,
+    ihdr: Option<IHDR>,
+    /// Created once IHDR gives the length of the image data.
+    inflater: Option<Inflater>,
+    // Decompressed data not yet unfiltered, or all decompressed data for interlaced images
+    scanlines: Vec<u8>,
+    prior_scanline: Vec<u8>,
+    row: u32,
+    finished: bool,
+}
+
+impl Default for StreamingDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamingDecoder {
+    pub fn new() -> StreamingDecoder {
+        StreamingDecoder {
+            crc_policy: CrcPolicy::default(),
+            buffer: vec![],
+            signature_read: false,
+            chunk: None,
+            ihdr: None,
+            inflater: None,
+            scanlines: vec![],
+            prior_scanline: vec![],
+            row: 0,
+            finished: false,
+        }
+    }
+
+    pub fn set_crc_policy(&mut self, crc_policy: CrcPolicy) {
+        self.crc_policy = crc_policy;
+    }
+
+    /// The image header, once it has been read.
+    pub fn ihdr(&self) -> Option<&IHDR> {
+        self.ihdr.as_ref()
+    }
+
+    /// True once the IEND chunk has been read.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Consumes the next fragment of the file and returns the events it completed. Data after IEND is ignored.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<Event>, PngError> {
+        let mut events = vec![];
+        if self.finished {
+            return Ok(events);
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        // Signature
+        if !self.signature_read {
+            if self.buffer.len() < SIGNATURE.len() {
+                return Ok(events);
+            }
+            PNG::check_signature(&self.buffer[..SIGNATURE.len()])?;
+            self.buffer.drain(..SIGNATURE.len());
+            self.signature_read = true;
+            events.push(Event::Signature);
+        }
+
+        // Chunks, data is processed as it arrives and the CRC checked once it is complete
+        while !self.finished {
+            let mut chunk = match self.chunk.take() {
+                Some(chunk) => { chunk }
+                None => {
+                    if self.buffer.len() < 8 {
+                        break;
+                    }
+                    self.start_chunk(&mut events)?
+                }
+            };
+
+            // Data
+            if chunk.remaining > 0 {
+                let num_bytes = min(chunk.remaining, self.buffer.len());
+                let data = self.buffer.drain(..num_bytes).collect::<Vec<_>>();
+                chunk.crc.update(&data);
+                chunk.remaining -= num_bytes;
+                self.process_data(&mut chunk, &data, &mut events)?;
+            }
+
+            // CRC
+            if chunk.remaining > 0 || self.buffer.len() < 4 {
+                self.chunk = Some(chunk);
+                break;
+            }
+            let stored = utils::read_be_u32(&self.buffer[..4]);
+            self.buffer.drain(..4);
+            self.complete_chunk(chunk, stored, &mut events)?;
+        }
+
+        Ok(events)
+    }
+
+    /// Reads the length and type of the next chunk.
+    fn start_chunk(&mut self, events: &mut Vec<Event>) -> Result<PartialChunk, PngError> {
+        let length = utils::read_be_u32(&self.buffer[..4]);
+        let chunk_type = String::from(Chunk::parse_chunk_type(&self.buffer[4..8])?);
+
+        // The first chunk must be IHDR
+        if self.ihdr.is_none() && chunk_type != "IHDR" {
+            return Err(PngError::MissingChunk(String::from("IHDR")));
+        }
+
+        let mut crc = Crc32::default();
+        crc.update(&self.buffer[4..8]);
+        self.buffer.drain(..8);
+        events.push(Event::ChunkStarted { chunk_type: chunk_type.clone(), length });
+
+        Ok(PartialChunk {
+            chunk_type,
+            length,
+            remaining: length as usize,
+            crc,
+            data: vec![],
+        })
+    }
+
+    fn process_data(&mut self, chunk: &mut PartialChunk, data: &[u8], events: &mut Vec<Event>) -> Result<(), PngError> {
+        match (chunk.chunk_type.as_str(), self.ihdr.clone()) {
+            ("IHDR", None) => {
+                // Anything past the 13 bytes of the header is ignored
+                let num_bytes = min(13_usize.saturating_sub(chunk.data.len()), data.len());
+                chunk.data.extend_from_slice(&data[..num_bytes]);
+            }
+            ("IDAT", Some(ihdr)) => {
+                if let Some(inflater) = &mut self.inflater {
+                    inflater.inflate(&mut &data[..], &mut self.scanlines, usize::MAX)?;
+                }
+                if matches!(ihdr.interlace_method, InterlaceMethod::None) {
+                    self.emit_rows(&ihdr, events)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn complete_chunk(&mut self, chunk: PartialChunk, stored_crc: u32, events: &mut Vec<Event>) -> Result<(), PngError> {
+        let computed_crc = chunk.crc.finish();
+        let crc_ok = stored_crc == computed_crc;
+        if !crc_ok && self.crc_policy == CrcPolicy::Error {
+            return Err(PngError::BadCrc {
+                chunk_type: chunk.chunk_type,
+                stored: stored_crc,
+                computed: computed_crc,
+            });
+        }
+
+        if chunk.chunk_type == "IHDR" && self.ihdr.is_none() {
+            let chunk_ref = ChunkRef {
+                chunk_type: &chunk.chunk_type,
+                data: &chunk.data,
+                crc: &[],
+            };
+            let ihdr = IHDR::try_from_chunk_ref(&chunk_ref)?;
+            self.prior_scanline = vec![0_u8; ihdr.scanline_length()? - 1];
+            self.inflater = Some(Inflater::new(ihdr.data_length()?));
+            self.ihdr = Some(ihdr.clone());
+            events.push(Event::Header(ihdr));
+        }
+
+        let is_end = chunk.chunk_type == "IEND";
+        events.push(Event::ChunkCompleted {
+            chunk_type: chunk.chunk_type,
+            length: chunk.length,
+            crc_ok,
+        });
+
+        if is_end {
+            self.finish(events)?;
+        }
+
+        Ok(())
+    }
+
+    /// Unfilters all complete scanlines that have been decompressed so far.
+    fn emit_rows(&mut self, ihdr: &IHDR, events: &mut Vec<Event>) -> Result<(), PngError> {
+        let scanline_length = ihdr.scanline_length()?;
+
+        let mut offset = 0;
+        while self.row < ihdr.height && self.scanlines.len() - offset >= scanline_length {
+            let scanline = &self.scanlines[offset..offset + scanline_length];
+            self.prior_scanline = PNG::apply_filter_scanlines(scanline, &self.prior_scanline, ihdr.filter_stride())?;
+
+            let data = if ihdr.bit_depth < 8 {
+                PNG::unpack_samples(&self.prior_scanline, ihdr, ihdr.width)
+            } else {
+                self.prior_scanline.clone()
+            };
+            events.push(Event::Row { index: self.row, data });
+
+            self.row += 1;
+            offset += scanline_length;
+        }
+        self.scanlines.drain(..offset);
+
+        Ok(())
+    }
+
+    fn finish(&mut self, events: &mut Vec<Event>) -> Result<(), PngError> {
+        let ihdr = self.ihdr.clone().ok_or(PngError::MissingChunk(String::from("IHDR")))?;
+
+        // Interlaced images can only be put together once all passes are available
+        if matches!(ihdr.interlace_method, InterlaceMethod::Adam7) {
+            let image = PNG::decode_adam7(&self.scanlines, &ihdr)?;
+            let row_length = ihdr.width as usize * ihdr.unpacked_bytes_per_pixel();
+            for (index, row) in image.chunks(row_length).enumerate() {
+                events.push(Event::Row { index: index as u32, data: row.to_vec() });
+            }
+            self.row = ihdr.height;
+        }
+
+        if self.row < ihdr.height {
+            return Err(PngError::Truncated);
+        }
+
+        self.finished = true;
+        events.push(Event::ImageFinished);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use crate::chunks::{ColorType, PLTE, tRNS_Indexed};
+use crate::error::PngError;
+use crate::png::{DecodedImage, GammaCorrection, PixelFormat};
+
+/// Replaces palette indices with their PLTE entries, adding alpha from tRNS if present.
+pub fn expand_palette(image: DecodedImage, plte: &PLTE, trns: Option<&tRNS_Indexed>) -> Result<DecodedImage, PngError> {
+    let channels = if trns.is_some() { 4 } else { 3 };
+    let alpha = |index: usize| trns
+        .and_then(|t| t.values().get(index).copied())
+        .unwrap_or(255);
+
+    let mut data = Vec::with_capacity(image.data.len() * channels);
+    for &index in &image.data {
+        let entry = plte.entries().get(index as usize)
+            .ok_or(PngError::PaletteIndexOutOfRange { index, palette_length: plte.entries().len() })?;
+
+        data.extend(entry);
+        if trns.is_some() {
+            data.push(alpha(index as usize));
+        }
+    }
+
+    Ok(DecodedImage {
+        color_type: if trns.is_some() { ColorType::TrueColorAlpha } else { ColorType::TrueColor },
+        bit_depth: 8,
+        data,
+        ..image
+    })
+}
+
+
+/// Reads the i-th sample, which is a big-endian pair of bytes for 16 bit images.
+fn read_sample(data: &[u8], i: usize, bit_depth: u8) -> u16 {
+    if bit_depth == 16 {
+        u16::from_be_bytes([data[2 * i], data[2 * i + 1]])
+    } else {
+        data[i] as u16
+    }
+}
+
+/// Rescales a sample to the range 0-255, rounding to the nearest value.
+fn scale_to_8(sample: u16, bit_depth: u8) -> u8 {
+    let max = (1_u32 << bit_depth) - 1;
+    ((sample as u32 * 255 + max / 2) / max) as u8
+}
+
+/// Converts a non-indexed image to an 8 bit pixel format. Pixels matching `color_key` become transparent.
+pub fn convert(image: DecodedImage, color_key: Option<&[u16]>, format: PixelFormat) -> DecodedImage {
+    let samples_per_pixel = image.color_type.samples_per_pixel() as usize;
+    let num_pixels = image.width as usize * image.height as usize;
+
+    let mut data = Vec::with_capacity(num_pixels * format.color_type().samples_per_pixel() as usize);
+    let mut samples = vec![0_u16; samples_per_pixel];
+    for pixel in 0..num_pixels {
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = read_sample(&image.data, pixel * samples_per_pixel + i, image.bit_depth);
+        }
+
+        // Everything goes through RGBA first
+        let [r, g, b, a] = match image.color_type {
+            ColorType::Greyscale | ColorType::IndexedColor => {
+                let grey = scale_to_8(samples[0], image.bit_depth);
+                let alpha = if color_key == Some(&samples[..]) { 0 } else { 255 };
+                [grey, grey, grey, alpha]
+            }
+            ColorType::GreyscaleAlpha => {
+                let grey = scale_to_8(samples[0], image.bit_depth);
+                [grey, grey, grey, scale_to_8(samples[1], image.bit_depth)]
+            }
+            ColorType::TrueColor => {
+                let alpha = if color_key == Some(&samples[..]) { 0 } else { 255 };
+                let [r, g, b] = [0, 1, 2].map(|i| scale_to_8(samples[i], image.bit_depth));
+                [r, g, b, alpha]
+            }
+            ColorType::TrueColorAlpha => {
+                [0, 1, 2, 3].map(|i| scale_to_8(samples[i], image.bit_depth))
+            }
+        };
+
+        // ITU-R BT.601 weights, greys stay exactly the same
+        let luma = || ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8;
+
+        match format {
+            PixelFormat::Rgba8 => { data.extend([r, g, b, a]) }
+            PixelFormat::Rgb8 => { data.extend([r, g, b]) }
+            PixelFormat::Luma8 => { data.push(luma()) }
+            PixelFormat::LumaA8 => { data.extend([luma(), a]) }
+        }
+    }
+
+    DecodedImage {
+        color_type: format.color_type(),
+        bit_depth: 8,
+        data,
+        ..image
+    }
+}
+
+/// How the samples of an image were encoded from linear light.
+pub enum TransferFunction {
+    Srgb,
+    /// Encoding exponent from gAMA, e.g. 0.45455
+    Gamma(f64),
+}
+
+impl TransferFunction {
+    /// Maps a sample in the range 0-1 back to linear light.
+    fn to_linear(&self, v: f64) -> f64 {
+        match self {
+            TransferFunction::Srgb => {
+                if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+            }
+            TransferFunction::Gamma(gamma) => { v.powf(1. / gamma) }
+        }
+    }
+}
+
+/// Re-encodes every colour sample with a lookup table, alpha is left as is.
+pub fn correct_gamma(mut image: DecodedImage, source: TransferFunction, target: GammaCorrection) -> Result<DecodedImage, PngError> {
+    if image.color_type == ColorType::IndexedColor {
+        return Err(PngError::UnsupportedFormat(String::from("Gamma correction of indexed color images requires expand_palette")));
+    }
+
+    let max = (1_u32 << image.bit_depth) - 1;
+    let table = (0..=max)
+        .map(|sample| {
+            let linear = source.to_linear(sample as f64 / max as f64);
+            let corrected = match target {
+                GammaCorrection::Display(gamma) => { linear.powf(1. / gamma) }
+                GammaCorrection::Linear => { linear }
+            };
+
+            (corrected * max as f64).round() as u16
+        })
+        .collect::<Vec<_>>();
+
+    let samples_per_pixel = image.color_type.samples_per_pixel() as usize;
+    let alpha_index = match image.color_type {
+        ColorType::GreyscaleAlpha | ColorType::TrueColorAlpha => { Some(samples_per_pixel - 1) }
+        _ => { None }
+    };
+
+    let num_samples = image.width as usize * image.height as usize * samples_per_pixel;
+    for i in (0..num_samples).filter(|i| Some(i % samples_per_pixel) != alpha_index) {
+        let sample = table[read_sample(&image.data, i, image.bit_depth) as usize];
+
+        if image.bit_depth == 16 {
+            image.data[2 * i..2 * i + 2].copy_from_slice(&sample.to_be_bytes());
+        } else {
+            image.data[i] = sample as u8;
+        }
+    }
+
+    Ok(image)
+}
+
+
+/// Rotates and flips the image so it displays upright, given an EXIF orientation. Orientations 5-8 swap the
+/// width and height, values outside 1-8 leave the image unchanged.
+pub fn orient(image: DecodedImage, orientation: u32) -> DecodedImage {
+    if !(2..=8).contains(&orientation) {
+        return image;
+    }
+
+    let sample_bytes = if image.bit_depth == 16 { 2 } else { 1 };
+    let pixel_bytes = image.color_type.samples_per_pixel() as usize * sample_bytes;
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (new_width, new_height) = if orientation >= 5 { (height, width) } else { (width, height) };
+
+    let mut data = Vec::with_capacity(image.data.len());
+    for y in 0..new_height {
+        for x in 0..new_width {
+            // Position of the pixel in the stored image
+            let (source_x, source_y) = match orientation {
+                2 => { (width - 1 - x, y) }
+                3 => { (width - 1 - x, height - 1 - y) }
+                4 => { (x, height - 1 - y) }
+                5 => { (y, x) }
+                6 => { (y, height - 1 - x) }
+                7 => { (width - 1 - y, height - 1 - x) }
+                _ => { (width - 1 - y, x) }
+            };
+
+            let start = (source_y * width + source_x) * pixel_bytes;
+            data.extend(&image.data[start..start + pixel_bytes]);
+        }
+    }
+
+    DecodedImage {
+        width: new_width as u32,
+        height: new_height as u32,
+        data,
+        ..image
+    }
+}
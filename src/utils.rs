@@ -1,13 +1,72 @@
-pub fn read_be_u32_mut(input: &mut &[u8]) -> u32 {
-    let (int_bytes, rest) = input.split_at(std::mem::size_of::<u32>());
-    *input = rest;
-    u32::from_be_bytes(int_bytes.try_into().unwrap())
+use miniz_oxide::inflate::stream::{self, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+use crate::error::PngError;
+
+const CRC_TABLE: [u32; 256] = make_crc_table();
+
+const fn make_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
+
+/// CRC-32 over several byte slices as if they were concatenated.
+/// https://www.w3.org/TR/png/#D-CRCAppendix
+pub fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = Crc32::default();
+    for &part in parts {
+        crc.update(part);
+    }
+
+    crc.finish()
+}
+
+/// CRC-32 of data that arrives in pieces.
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32(0xFFFFFFFF)
+    }
 }
 
-pub fn read_str_mut(input: &mut &[u8], num_bytes: usize) -> String {
-    let (int_bytes, rest) = input.split_at(num_bytes);
+impl Crc32 {
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = CRC_TABLE[((self.0 ^ byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.0 ^ 0xFFFFFFFF
+    }
+}
+
+pub fn read_bytes_mut<'a>(input: &mut &'a [u8], num_bytes: usize) -> Result<&'a [u8], PngError> {
+    if input.len() < num_bytes {
+        return Err(PngError::Truncated);
+    }
+
+    let (bytes, rest) = input.split_at(num_bytes);
     *input = rest;
-    String::from_utf8(int_bytes.to_vec()).unwrap()
+    Ok(bytes)
+}
+
+pub fn read_be_u32_mut(input: &mut &[u8]) -> Result<u32, PngError> {
+    let int_bytes = read_bytes_mut(input, std::mem::size_of::<u32>())?;
+    Ok(u32::from_be_bytes(int_bytes.try_into().unwrap()))
 }
 
 
@@ -26,4 +85,71 @@ pub fn read_until_null(input: &[u8]) -> Vec<u8> {
         .take_while(|&&x| x != 0)
         .copied()
         .collect()
-}
\ No newline at end of file
+}
+
+/// Incremental zlib decompression, used for all compressed data so that every stream gets the same limits and
+/// errors.
+pub struct Inflater {
+    state: Box<InflateState>,
+    limit: usize,
+    inflated: usize,
+    finished: bool,
+}
+
+impl Inflater {
+    /// Fails once the stream decompresses to more than `limit` bytes.
+    pub fn new(limit: usize) -> Inflater {
+        Inflater {
+            state: InflateState::new_boxed(DataFormat::Zlib),
+            limit,
+            inflated: 0,
+            finished: false,
+        }
+    }
+
+    /// True once the end of the stream has been reached.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Decompresses from the front of `input` into `output` until `output` holds at least `wanted` bytes, the
+    /// input is used up or the stream ends. Consumed input is removed from `input`. Returns true once the end of
+    /// the stream has been reached, data after it is ignored.
+    pub fn inflate(&mut self, input: &mut &[u8], output: &mut Vec<u8>, wanted: usize) -> Result<bool, PngError> {
+        let mut buffer = [0_u8; 8192];
+        while !self.finished && output.len() < wanted {
+            let result = stream::inflate(&mut self.state, input, &mut buffer, MZFlush::None);
+            *input = &input[result.bytes_consumed..];
+            output.extend_from_slice(&buffer[..result.bytes_written]);
+
+            self.inflated += result.bytes_written;
+            if self.inflated > self.limit {
+                return Err(PngError::Decompress(format!("Decompressed data exceeds {} bytes", self.limit)));
+            }
+
+            match result.status {
+                Ok(MZStatus::StreamEnd) => { self.finished = true }
+                Ok(_) => {
+                    if result.bytes_consumed == 0 && result.bytes_written == 0 {
+                        break;
+                    }
+                }
+                // No progress possible until more input arrives
+                Err(MZError::Buf) => { break }
+                Err(e) => { return Err(PngError::Decompress(format!("{:?}", e))) }
+            }
+        }
+
+        Ok(self.finished)
+    }
+}
+
+/// Decompresses a complete zlib stream of at most `limit` bytes.
+pub fn inflate(mut compressed: &[u8], limit: usize) -> Result<Vec<u8>, PngError> {
+    let mut decompressed = vec![];
+    if !Inflater::new(limit).inflate(&mut compressed, &mut decompressed, usize::MAX)? {
+        return Err(PngError::Decompress(String::from("Unexpected end of zlib stream")));
+    }
+
+    Ok(decompressed)
+}
@@ -1,48 +1,230 @@
 use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 
-use png_reader::chunks::{bKGD_Greyscale, cHRM, eXIf, FromChunk, iCCP, IHDR, iTXt, PLTE, tEXt, tIME, tRNS_Indexed, zTXt};
-use png_reader::png::PNG;
+use png_reader::chunks::{acTL, BlendOp, ByteAlign, CompressionMethod, DisposeOp, fcTL, gAMA, InterlaceMethod, MAX_INFLATED_LENGTH, ParsedChunk, pHYs, PixelUnit, RenderingIntent, sRGB, bKGD_Greyscale, cHRM, ColorType, eXIf, FromChunk, iCCP, IHDR, iTXt, PLTE, tEXt, tIME, tRNS_Indexed, TryFromChunk, zTXt};
+use png_reader::error::PngError;
+use png_reader::exif::{tag_name, ExifValue, IfdKind};
+use png_reader::metadata::{Background, Chromaticities, ModificationTime, Transparency};
+use png_reader::reader::ChunkReader;
+use png_reader::png::{Chunk, CrcPolicy, PngRef, DecodeOptions, Endianness, GammaCorrection, PixelFormat, PNG};
+use png_reader::stream::{Event, StreamingDecoder};
+
+/// Writes bytes to a temp file, returning its path.
+fn write_temp(name: &str, bytes: &[u8]) -> String {
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, bytes).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn encode_indexed(width: u32, height: u32, bit_depth: png::BitDepth, palette: &[u8], trns: Option<&[u8]>, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(bit_depth);
+    encoder.set_palette(palette.to_vec());
+    if let Some(trns) = trns {
+        encoder.set_trns(trns.to_vec());
+    }
+    encoder.write_header().unwrap()
+        .write_image_data(data).unwrap();
+
+    bytes
+}
+
+/// Packs one-byte samples into rows of `bit_depth` bits each.
+fn pack_samples(samples: &[u8], width: usize, bit_depth: usize) -> Vec<u8> {
+    samples.chunks(width)
+        .flat_map(|row| {
+            let mut packed = vec![0_u8; (width * bit_depth).div_ceil(8)];
+            for (i, &sample) in row.iter().enumerate() {
+                let bit = i * bit_depth;
+                packed[bit / 8] |= sample << (8 - bit_depth - bit % 8);
+            }
+            packed
+        })
+        .collect()
+}
+
+/// Encodes an image with the reference `png` crate.
+fn encode_png(width: u32, height: u32, color_type: png::ColorType, bit_depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+    encode_png_filtered(width, height, color_type, bit_depth, png::FilterType::NoFilter, data)
+}
+
+fn encode_png_filtered(width: u32, height: u32, color_type: png::ColorType, bit_depth: png::BitDepth, filter: png::FilterType, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+    encoder.set_filter(filter);
+    encoder.write_header().unwrap()
+        .write_image_data(data).unwrap();
+
+    bytes
+}
+
+/// Decodes an image with the reference `png` crate.
+fn decode_png(bytes: &[u8]) -> Vec<u8> {
+    decode_png_with(bytes, png::Transformations::IDENTITY)
+}
+
+fn decode_png_with(bytes: &[u8], transformations: png::Transformations) -> Vec<u8> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(transformations);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+
+    buf
+}
+
+/// Assembles a PNG file from raw chunks.
+fn build_png(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    for (chunk_type, data) in chunks {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(*chunk_type);
+        hasher.update(data);
+
+        bytes.extend((data.len() as u32).to_be_bytes());
+        bytes.extend(*chunk_type);
+        bytes.extend(data);
+        bytes.extend(hasher.finalize().to_be_bytes());
+    }
+
+    bytes
+}
+
+fn ihdr_data(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace_method: u8) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend(width.to_be_bytes());
+    data.extend(height.to_be_bytes());
+    data.extend([bit_depth, color_type, 0, 0, interlace_method]);
+
+    data
+}
+
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Splits byte aligned pixels into unfiltered Adam7 passes.
+fn adam7_scanlines(data: &[u8], width: usize, height: usize, pixel_bytes: usize) -> Vec<u8> {
+    let passes = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+    let mut scanlines = vec![];
+    for (x0, y0, dx, dy) in passes {
+        if x0 >= width || y0 >= height {
+            continue;
+        }
+        for y in (y0..height).step_by(dy) {
+            scanlines.push(0);
+            for x in (x0..width).step_by(dx) {
+                let offset = (y * width + x) * pixel_bytes;
+                scanlines.extend(&data[offset..offset + pixel_bytes]);
+            }
+        }
+    }
+
+    scanlines
+}
+
+/// Splits pixels into Adam7 passes and filters them, alternating between Sub and Paeth from one scanline to the
+/// next. Pixels are `pixel_bytes` bytes each, or a single sample of `bit_depth` bits which is packed.
+fn adam7_filtered(data: &[u8], width: usize, height: usize, bit_depth: usize, pixel_bytes: usize) -> Vec<u8> {
+    let passes = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+    let stride = if bit_depth < 8 { 1 } else { pixel_bytes };
+    let paeth = |a: u8, b: u8, c: u8| {
+        let p = a as i32 + b as i32 - c as i32;
+        let (pa, pb, pc) = (p.abs_diff(a as i32), p.abs_diff(b as i32), p.abs_diff(c as i32));
+        if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+    };
+
+    let mut scanlines = vec![];
+    for (pass, (x0, y0, dx, dy)) in passes.into_iter().enumerate() {
+        if x0 >= width || y0 >= height {
+            continue;
+        }
+
+        // Every pass starts from a blank prior scanline
+        let mut prior = vec![];
+        for (row, y) in (y0..height).step_by(dy).enumerate() {
+            let pixels = (x0..width).step_by(dx)
+                .flat_map(|x| &data[(y * width + x) * pixel_bytes..(y * width + x + 1) * pixel_bytes])
+                .copied()
+                .collect::<Vec<_>>();
+            let current = if bit_depth < 8 { pack_samples(&pixels, pixels.len(), bit_depth) } else { pixels };
+            prior.resize(current.len(), 0);
+
+            let filter_type = if (pass + row) % 2 == 0 { 1 } else { 4 };
+            scanlines.push(filter_type);
+            for i in 0..current.len() {
+                let left = if i < stride { 0 } else { current[i - stride] };
+                let up_left = if i < stride { 0 } else { prior[i - stride] };
+                let prediction = if filter_type == 1 { left } else { paeth(left, prior[i], up_left) };
+                scanlines.push(current[i].wrapping_sub(prediction));
+            }
+            prior = current;
+        }
+    }
+
+    scanlines
+}
+
+/// Deterministic noise, so that every filter type has something to do.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 12345_u32;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
 
 #[test]
 fn wormomancer() {
     let png_path = "/pictures/wormomancer.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
-    assert_eq!(png.get_image_data().len(), 756501);
+    assert_eq!(png.get_image_data().unwrap().len(), 756501);
 }
 
 #[test]
 fn caught() {
     let png_path = r"D:\Pictures\caught.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
 
     println!("{:?}", IHDR::from_chunk(&png.chunks[0]));
     println!("{:?}", eXIf::from_chunk(&png.chunks[4]));
 
-    assert_eq!(png.get_image_data().len(), 124928);
+    assert_eq!(png.get_image_data().unwrap().len(), 124928);
 }
 
 #[test]
 fn broad_bolts() {
     let png_path = r"D:\Pictures\broad bolts.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
 
     println!("{:?}", IHDR::from_chunk(&png.chunks[0]));
 
-    assert_eq!(png.get_image_data().len(), 2256000);
+    assert_eq!(png.get_image_data().unwrap().len(), 2256000);
 }
 
 #[test]
 fn gasm() {
     let png_path = r"D:\\Pictures\\145-1455614_captain-gachi-gasm.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
     png.chunks.iter().enumerate().for_each(|(i, c)| println!("{i} {} {}", c, c.data.len()));
 
@@ -51,14 +233,14 @@ fn gasm() {
     println!("{:?}", bKGD_Greyscale::from_chunk(&png.chunks[4]));
     println!("{:?}", tEXt::from_chunk(&png.chunks[11]));
 
-    assert_eq!(png.get_image_data().len(), 481636);
+    assert_eq!(png.get_image_data().unwrap().len(), 481636);
 }
 
 #[test]
 fn four_set() {
     let png_path = r"D:\\Pictures\\4set.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
     png.chunks.iter().enumerate().for_each(|(i, c)| println!("{i} {} {}", c, c.data.len()));
 
@@ -66,14 +248,14 @@ fn four_set() {
     println!("{:?}", iCCP::from_chunk(&png.chunks[1]));
     println!("{:?}", tIME::from_chunk(&png.chunks[4]));
 
-    assert_eq!(png.get_image_data().len(), 2359296);
+    assert_eq!(png.get_image_data().unwrap().len(), 2359296);
 }
 
 #[test]
 fn blaspgemy() {
     let png_path = r"D:\\Pictures\\blaspgemy.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
     png.chunks.iter().enumerate().for_each(|(i, c)| println!("{i} {}", c));
 
@@ -82,14 +264,14 @@ fn blaspgemy() {
     println!("{:?}", iCCP::from_chunk(&png.chunks[2]));
     println!("{:?}", tIME::from_chunk(&png.chunks[4]));
 
-    assert_eq!(png.get_image_data().len(), 12589056);
+    assert_eq!(png.get_image_data().unwrap().len(), 12589056);
 }
 
 #[test]
 fn covfefe() {
     let png_path = r"D:\\Pictures\\covfefe.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
     png.chunks.iter().enumerate().for_each(|(i, c)| println!("{i} {}", c));
 
@@ -97,14 +279,14 @@ fn covfefe() {
     println!("{:?}", PLTE::from_chunk(&png.chunks[3]));
     println!("{:?}", tRNS_Indexed::from_chunk(&png.chunks[4]));
 
-    assert_eq!(png.get_image_data().len(), 5796000);
+    assert_eq!(png.get_image_data().unwrap().len(), 5796000);
 }
 
 #[test]
 fn crazy_champ() {
     let png_path = r"D:\\Pictures\\crazyChamp.png";
 
-    let png = PNG::open(png_path);
+    let png = PNG::open(png_path).unwrap();
     println!("{:?}", png);
     png.chunks.iter().enumerate().for_each(|(i, c)| println!("{i} {}", c));
 
@@ -112,7 +294,7 @@ fn crazy_champ() {
     println!("{:?}", cHRM::from_chunk(&png.chunks[1]));
     println!("{:?}", iTXt::from_chunk(&png.chunks[3]));
 
-    assert_eq!(png.get_image_data().len(), 50176);
+    assert_eq!(png.get_image_data().unwrap().len(), 50176);
 }
 
 #[test]
@@ -127,7 +309,7 @@ fn crawl() {
                     if ext.to_str().unwrap() == "png" {
                         println!("{:?}", item.path());
 
-                        let png = PNG::open(item.path().to_str().unwrap());
+                        let png = PNG::open(item.path().to_str().unwrap()).unwrap();
                         let chunk_types = png.chunks.iter().map(|c| c.chunk_type.as_str())
                             .collect::<Vec<_>>();
 
@@ -143,4 +325,1228 @@ fn crawl() {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn missing_file() {
+    assert!(matches!(PNG::open("/does/not/exist.png"), Err(PngError::Io(_))));
+}
+
+#[test]
+fn bad_signature() {
+    let png_path = write_temp("bad_signature.png", b"GIF89a");
+
+    assert!(matches!(PNG::open(&png_path), Err(PngError::BadSignature(_))));
+}
+
+#[test]
+fn truncated() {
+    let mut bytes = encode_png(4, 4, png::ColorType::Grayscale, png::BitDepth::Eight, &[0; 16]);
+    bytes.truncate(bytes.len() - 20);
+    let png_path = write_temp("truncated.png", &bytes);
+
+    assert!(matches!(PNG::open(&png_path), Err(PngError::Truncated)));
+}
+
+#[test]
+fn invalid_ihdr() {
+    // Color type 5 does not exist
+    let mut bytes: &[u8] = &[0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 5, 0, 0, 0, 0, 0, 0, 0];
+    let chunk = Chunk::from_byte_stream(&mut bytes, CrcPolicy::Ignore).unwrap();
+
+    assert!(matches!(IHDR::try_from_chunk(&chunk), Err(PngError::InvalidChunk { .. })));
+
+    // Dimensions are limited to 2^31-1
+    let bytes = build_png(&[(b"IHDR", ihdr_data(1 << 31, 1, 8, 0, 0))]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().ihdr(), Err(PngError::InvalidChunk { .. })));
+
+    // Compression and filter method 0 are the only ones defined
+    for method in [10, 11] {
+        let mut data = ihdr_data(1, 1, 8, 0, 0);
+        data[method] = 1;
+        let bytes = build_png(&[(b"IHDR", data)]);
+        assert!(matches!(PNG::from_bytes(&bytes).unwrap().ihdr(), Err(PngError::UnsupportedFormat(_))));
+    }
+}
+
+#[test]
+fn idat_bomb() {
+    // Inflating stops just past the 2 bytes of scanlines the image needs
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(1, 1, 8, 0, 0)),
+        (b"IDAT", zlib_compress(&vec![0; 64 * 1024 * 1024])),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().decode(), Err(PngError::Decompress(_))));
+    assert!(matches!(StreamingDecoder::new().feed(&bytes), Err(PngError::Decompress(_))));
+    let (_, report) = PNG::from_bytes(&bytes).unwrap().decode_lenient(&[0]).unwrap();
+    assert!(matches!(report.errors[..], [PngError::Decompress(_)]));
+}
+
+#[test]
+fn oversized_image() {
+    // Valid dimensions whose buffer sizes overflow must not panic or under-allocate
+    let max = (1 << 31) - 1;
+    for interlace_method in [0, 1] {
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(max, max, 16, 6, interlace_method)),
+            (b"IDAT", zlib_compress(&[0; 64])),
+            (b"IEND", vec![]),
+        ]);
+        let png = PNG::from_bytes(&bytes).unwrap();
+        assert!(matches!(png.decode(), Err(PngError::UnsupportedFormat(_))));
+        assert!(matches!(png.decode_lenient(&[0; 8]), Err(PngError::UnsupportedFormat(_))));
+    }
+}
+
+#[test]
+fn truncated_interlaced_image() {
+    // A few bytes of image data must not allocate the 28.8 GB the dimensions call for
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(60000, 60000, 16, 6, 1)),
+        (b"IDAT", zlib_compress(&[0; 64])),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().decode(), Err(PngError::Truncated)));
+    assert!(matches!(StreamingDecoder::new().feed(&bytes), Err(PngError::Truncated)));
+}
+
+#[test]
+fn greyscale_8() {
+    let data = (0..=255).collect::<Vec<u8>>();
+    let png_path = write_temp("greyscale_8.png", &encode_png(16, 16, png::ColorType::Grayscale, png::BitDepth::Eight, &data));
+
+    let png = PNG::open(&png_path).unwrap();
+    assert_eq!(png.get_image_data().unwrap(), data);
+}
+
+#[test]
+fn crc() {
+    let data = (0..=255).collect::<Vec<u8>>();
+    let mut bytes = encode_png(16, 16, png::ColorType::Grayscale, png::BitDepth::Eight, &data);
+    let png_path = write_temp("crc.png", &bytes);
+    assert!(PNG::open(&png_path).unwrap().chunks.iter().all(|c| c.crc_ok()));
+
+    // Flip a bit in the IHDR height
+    bytes[8 + 8 + 7] ^= 1;
+    let png_path = write_temp("bad_crc.png", &bytes);
+    assert!(matches!(PNG::open(&png_path), Err(PngError::BadCrc { .. })));
+
+    let png = PNG::open_with_crc_policy(&png_path, CrcPolicy::Ignore).unwrap();
+    assert!(!png.chunks[0].crc_ok());
+    assert_ne!(png.chunks[0].stored_crc(), png.chunks[0].computed_crc());
+
+    // Warn keeps the chunk and reports the mismatch to the caller
+    let png = PNG::open_with_crc_policy(&png_path, CrcPolicy::Warn).unwrap();
+    let errors = png.crc_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], PngError::BadCrc { chunk_type, .. } if chunk_type == "IHDR"));
+    assert!(PNG::from_bytes(&encode_png(1, 1, png::ColorType::Grayscale, png::BitDepth::Eight, &[0])).unwrap().crc_errors().is_empty());
+}
+
+#[test]
+fn greyscale_bit_depths() {
+    // Odd width so rows end in padding bits
+    let (width, height) = (13, 5);
+
+    for (bit_depth, depth) in [(1, png::BitDepth::One), (2, png::BitDepth::Two), (4, png::BitDepth::Four)] {
+        let samples = (0..width * height).map(|i| (i % (1 << bit_depth)) as u8).collect::<Vec<_>>();
+        let bytes = encode_png(width as u32, height as u32, png::ColorType::Grayscale, depth, &pack_samples(&samples, width, bit_depth));
+        let png_path = write_temp(&format!("greyscale_{}.png", bit_depth), &bytes);
+
+        let image = PNG::open(&png_path).unwrap().decode().unwrap();
+        assert_eq!(image.bit_depth, bit_depth as u8);
+        assert_eq!(image.data, samples);
+    }
+
+    let samples = (0..width * height).flat_map(|i| (i as u16 * 1000).to_be_bytes()).collect::<Vec<_>>();
+    let bytes = encode_png(width as u32, height as u32, png::ColorType::Grayscale, png::BitDepth::Sixteen, &samples);
+    let png_path = write_temp("greyscale_16.png", &bytes);
+
+    let image = PNG::open(&png_path).unwrap().decode().unwrap();
+    assert_eq!(image.bit_depth, 16);
+    assert_eq!(image.data, samples);
+}
+
+#[test]
+fn indexed_bit_depths() {
+    // Odd width so rows end in padding bits
+    let (width, height) = (13, 5);
+
+    for (bit_depth, depth) in [(1, png::BitDepth::One), (2, png::BitDepth::Two), (4, png::BitDepth::Four)] {
+        let num_entries = 1 << bit_depth;
+        let palette = noise(num_entries * 3);
+        let indices = (0..width * height).map(|i| ((i * 7 + i / width) % num_entries) as u8).collect::<Vec<_>>();
+        let bytes = encode_indexed(width as u32, height as u32, depth, &palette, None, &pack_samples(&indices, width, bit_depth));
+        let png = PNG::from_bytes(&bytes).unwrap();
+
+        let image = png.decode().unwrap();
+        assert_eq!((image.color_type, image.bit_depth), (ColorType::IndexedColor, bit_depth as u8));
+        assert_eq!(image.data, indices);
+
+        let expanded = png.decode_with(&DecodeOptions { expand_palette: true, ..Default::default() }).unwrap();
+        assert_eq!(expanded.data, indices.iter().flat_map(|&i| &palette[i as usize * 3..i as usize * 3 + 3]).copied().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn filters_match_reference() {
+    let (width, height) = (17, 9);
+    let formats = [
+        (png::ColorType::Grayscale, png::BitDepth::Eight, 1),
+        (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, 2),
+        (png::ColorType::Rgb, png::BitDepth::Eight, 3),
+        (png::ColorType::Rgba, png::BitDepth::Eight, 4),
+        (png::ColorType::Rgb, png::BitDepth::Sixteen, 6),
+        (png::ColorType::Rgba, png::BitDepth::Sixteen, 8),
+    ];
+    let filters = [
+        png::FilterType::NoFilter,
+        png::FilterType::Sub,
+        png::FilterType::Up,
+        png::FilterType::Avg,
+        png::FilterType::Paeth,
+    ];
+
+    for (color_type, bit_depth, bytes_per_pixel) in formats {
+        for filter in filters {
+            let data = noise(width * height * bytes_per_pixel);
+            let bytes = encode_png_filtered(width as u32, height as u32, color_type, bit_depth, filter, &data);
+            let png_path = write_temp(&format!("filter_{:?}_{:?}_{:?}.png", color_type, bit_depth, filter), &bytes);
+
+            let decoded = PNG::open(&png_path).unwrap().get_image_data().unwrap();
+            assert_eq!(decoded, decode_png(&bytes), "{:?} {:?} {:?}", color_type, bit_depth, filter);
+            assert_eq!(decoded, data);
+        }
+    }
+}
+
+#[test]
+fn adam7() {
+    // (width, height, bit depth, color type, bytes per pixel)
+    let formats = [(13, 11, 8, 2, 3), (9, 17, 16, 6, 8), (1, 1, 8, 0, 1), (3, 2, 8, 4, 2)];
+
+    for (width, height, bit_depth, color_type, pixel_bytes) in formats {
+        let data = noise(width * height * pixel_bytes);
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(width as u32, height as u32, bit_depth, color_type, 1)),
+            (b"IDAT", zlib_compress(&adam7_scanlines(&data, width, height, pixel_bytes))),
+            (b"IEND", vec![]),
+        ]);
+        let png_path = write_temp(&format!("adam7_{}x{}.png", width, height), &bytes);
+
+        let decoded = PNG::open(&png_path).unwrap().get_image_data().unwrap();
+        assert_eq!(decoded, decode_png(&bytes));
+        assert_eq!(decoded, data);
+    }
+
+    // Sub and Paeth filters, the filter state has to be reset at the start of each pass
+    for (width, height, bit_depth, color_type, pixel_bytes) in formats {
+        let data = noise(width * height * pixel_bytes);
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(width as u32, height as u32, bit_depth, color_type, 1)),
+            (b"IDAT", zlib_compress(&adam7_filtered(&data, width, height, bit_depth as usize, pixel_bytes))),
+            (b"IEND", vec![]),
+        ]);
+
+        let decoded = PNG::from_bytes(&bytes).unwrap().get_image_data().unwrap();
+        assert_eq!(decoded, decode_png(&bytes));
+        assert_eq!(decoded, data);
+    }
+
+    // Sub-byte greyscale, where pass widths are not byte aligned and scanlines end in padding bits
+    for (width, height, bit_depth) in [(11, 9, 1), (13, 7, 2), (5, 10, 4), (3, 3, 1)] {
+        let samples = noise(width * height).iter().map(|s| s % (1 << bit_depth)).collect::<Vec<_>>();
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(width as u32, height as u32, bit_depth as u8, 0, 1)),
+            (b"IDAT", zlib_compress(&adam7_filtered(&samples, width, height, bit_depth, 1))),
+            (b"IEND", vec![]),
+        ]);
+
+        let image = PNG::from_bytes(&bytes).unwrap().decode().unwrap();
+        assert_eq!(image.bit_depth, bit_depth as u8);
+        assert_eq!(image.data, samples);
+    }
+}
+
+#[test]
+fn expand_palette() {
+    let (width, height) = (7, 5);
+    let palette = noise(16 * 3);
+    let indices = (0..width * height).map(|i| (i % 16) as u8).collect::<Vec<_>>();
+    let packed = pack_samples(&indices, width, 4);
+    let options = DecodeOptions { expand_palette: true, ..Default::default() };
+
+    // RGB
+    let bytes = encode_indexed(width as u32, height as u32, png::BitDepth::Four, &palette, None, &packed);
+    let png_path = write_temp("expand_palette_rgb.png", &bytes);
+    let image = PNG::open(&png_path).unwrap().decode_with(&options).unwrap();
+    assert_eq!(image.color_type, ColorType::TrueColor);
+    assert_eq!(image.data, decode_png_with(&bytes, png::Transformations::EXPAND));
+
+    // RGBA, with fewer tRNS entries than palette entries
+    let bytes = encode_indexed(width as u32, height as u32, png::BitDepth::Four, &palette, Some(&[0, 64, 128]), &packed);
+    let png_path = write_temp("expand_palette_rgba.png", &bytes);
+    let image = PNG::open(&png_path).unwrap().decode_with(&options).unwrap();
+    assert_eq!(image.color_type, ColorType::TrueColorAlpha);
+    assert_eq!(image.data, decode_png_with(&bytes, png::Transformations::EXPAND));
+}
+
+#[test]
+fn expand_palette_errors() {
+    let options = DecodeOptions { expand_palette: true, ..Default::default() };
+
+    // Index 2 with only 2 palette entries
+    let bytes = encode_indexed(2, 1, png::BitDepth::Eight, &[0, 0, 0, 255, 255, 255], None, &[1, 2]);
+    let png_path = write_temp("palette_out_of_range.png", &bytes);
+    let result = PNG::open(&png_path).unwrap().decode_with(&options);
+    assert!(matches!(result, Err(PngError::PaletteIndexOutOfRange { index: 2, palette_length: 2 })));
+
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(2, 1, 8, 3, 0)),
+        (b"IDAT", zlib_compress(&[0, 1, 2])),
+        (b"IEND", vec![]),
+    ]);
+    let png_path = write_temp("palette_missing.png", &bytes);
+    let result = PNG::open(&png_path).unwrap().decode_with(&options);
+    assert!(matches!(result, Err(PngError::MissingChunk(_))));
+}
+
+#[test]
+fn decode_as() {
+    // 2 bit greyscale with a tRNS colour key
+    let samples = [0, 1, 2, 3];
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(4, 1, 2, 0, 0)),
+        (b"tRNS", vec![0, 2]),
+        (b"IDAT", zlib_compress(&[0, 0b00_01_10_11])),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::open(&write_temp("decode_as_grey.png", &bytes)).unwrap();
+    assert_eq!(png.decode().unwrap().data, samples);
+    assert_eq!(
+        png.decode_as(PixelFormat::Rgba8).unwrap().data,
+        [0, 0, 0, 255, 85, 85, 85, 255, 170, 170, 170, 0, 255, 255, 255, 255],
+    );
+    assert_eq!(png.decode_as(PixelFormat::LumaA8).unwrap().data, [0, 255, 85, 255, 170, 0, 255, 255]);
+    assert_eq!(png.decode_as(PixelFormat::Luma8).unwrap().data, [0, 85, 170, 255]);
+
+    // 16 bit RGB scaled down to 8 bits
+    let samples = [0_u16, 257 * 10, 65535, 257 * 200, 257 * 100, 257]
+        .iter()
+        .flat_map(|x| x.to_be_bytes())
+        .collect::<Vec<_>>();
+    let bytes = encode_png(2, 1, png::ColorType::Rgb, png::BitDepth::Sixteen, &samples);
+    let png = PNG::open(&write_temp("decode_as_rgb16.png", &bytes)).unwrap();
+    let image = png.decode_as(PixelFormat::Rgba8).unwrap();
+    assert_eq!((image.color_type, image.bit_depth), (ColorType::TrueColorAlpha, 8));
+    assert_eq!(image.data, [0, 10, 255, 255, 200, 100, 1, 255]);
+
+    // RGBA to luma
+    let bytes = encode_png(2, 1, png::ColorType::Rgba, png::BitDepth::Eight, &[255, 0, 0, 10, 50, 50, 50, 20]);
+    let png = PNG::open(&write_temp("decode_as_rgba.png", &bytes)).unwrap();
+    assert_eq!(png.decode_as(PixelFormat::LumaA8).unwrap().data, [76, 10, 50, 20]);
+    assert_eq!(png.decode_as(PixelFormat::Rgb8).unwrap().data, [255, 0, 0, 50, 50, 50]);
+}
+
+#[test]
+fn decode_u16() {
+    let samples = (0..12).map(|i| i * 5000 + 1).collect::<Vec<u16>>();
+    let bytes = encode_png(2, 2, png::ColorType::Rgb, png::BitDepth::Sixteen, &samples.iter().flat_map(|x| x.to_be_bytes()).collect::<Vec<_>>());
+    let png = PNG::open(&write_temp("decode_u16.png", &bytes)).unwrap();
+
+    assert_eq!(png.decode_u16().unwrap(), samples);
+    assert_eq!(
+        png.decode_u16_with(Endianness::Big).unwrap(),
+        samples.iter().map(|x| x.to_be()).collect::<Vec<_>>(),
+    );
+
+    let bytes = encode_png(2, 2, png::ColorType::Grayscale, png::BitDepth::Eight, &[0; 4]);
+    let png = PNG::open(&write_temp("decode_u16_8bit.png", &bytes)).unwrap();
+    assert!(matches!(png.decode_u16(), Err(PngError::UnsupportedFormat(_))));
+}
+
+#[test]
+fn gamma_correction() {
+    let linear = DecodeOptions { gamma_correction: Some(GammaCorrection::Linear), ..Default::default() };
+    let display = DecodeOptions { gamma_correction: Some(GammaCorrection::Display(2.)), ..Default::default() };
+
+    // Greyscale + alpha with a gamma of 0.5, alpha is never touched
+    let scanline = vec![0, 0, 128, 128, 128, 255, 255];
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 1, 8, 4, 0)),
+        (b"gAMA", 50000_u32.to_be_bytes().to_vec()),
+        (b"IDAT", zlib_compress(&scanline)),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::open(&write_temp("gamma_gama.png", &bytes)).unwrap();
+    assert_eq!(png.decode_with(&linear).unwrap().data, [0, 128, 64, 128, 255, 255]);
+    assert_eq!(png.decode_with(&display).unwrap().data, [0, 128, 128, 128, 255, 255]);
+
+    // sRGB overrides gAMA
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 1, 8, 0, 0)),
+        (b"sRGB", vec![0]),
+        (b"gAMA", 50000_u32.to_be_bytes().to_vec()),
+        (b"IDAT", zlib_compress(&[0, 0, 128, 255])),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::open(&write_temp("gamma_srgb.png", &bytes)).unwrap();
+    assert_eq!(png.decode_with(&linear).unwrap().data, [0, 55, 255]);
+
+    // No gamma information
+    let bytes = encode_png(3, 1, png::ColorType::Grayscale, png::BitDepth::Eight, &[0, 128, 255]);
+    let png = PNG::open(&write_temp("gamma_none.png", &bytes)).unwrap();
+    assert_eq!(png.decode_with(&linear).unwrap().data, [0, 128, 255]);
+
+    // Display gamma that would turn the image black or white
+    for gamma in [0., -2.2, f64::NAN, f64::INFINITY] {
+        let options = DecodeOptions { gamma_correction: Some(GammaCorrection::Display(gamma)), ..Default::default() };
+        assert!(matches!(png.decode_with(&options), Err(PngError::InvalidArgument(_))));
+    }
+}
+
+#[test]
+fn icc_profile() {
+    let mut profile = vec![0_u8; 200];
+    profile[..4].copy_from_slice(&200_u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+
+    let iccp_data = |profile: &[u8]| [b"Test profile\0\0".to_vec(), zlib_compress(profile)].concat();
+    let build = |name: &str, iccp: Vec<u8>| {
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(1, 1, 8, 0, 0)),
+            (b"iCCP", iccp),
+            (b"IDAT", zlib_compress(&[0, 0])),
+            (b"IEND", vec![]),
+        ]);
+        PNG::open(&write_temp(name, &bytes)).unwrap()
+    };
+
+    let png = build("icc_profile.png", iccp_data(&profile));
+    let iccp = png.icc_profile().unwrap().unwrap();
+    assert_eq!(iccp.profile_name(), "Test profile");
+    assert_eq!(iccp.profile(), profile);
+
+    profile[36..40].copy_from_slice(b"nope");
+    let png = build("icc_profile_bad.png", iccp_data(&profile));
+    assert!(matches!(png.icc_profile(), Err(PngError::InvalidChunk { .. })));
+
+    // A zlib bomb is cut off at the limit
+    let bomb = zlib_compress(&vec![0; MAX_INFLATED_LENGTH + 1]);
+    let png = build("icc_profile_bomb.png", [b"Test profile\0\0".to_vec(), bomb.clone()].concat());
+    assert!(matches!(png.icc_profile(), Err(PngError::Decompress(_))));
+    let itxt = [b"Comment\0\x01\0\0\0".to_vec(), bomb].concat();
+    let chunk = PNG::from_bytes(&build_png(&[(b"iTXt", itxt)])).unwrap().chunks.remove(0);
+    assert!(matches!(iTXt::try_from_chunk(&chunk), Err(PngError::Decompress(_))));
+
+    let png = PNG::open(&write_temp("icc_profile_none.png", &encode_png(1, 1, png::ColorType::Grayscale, png::BitDepth::Eight, &[0]))).unwrap();
+    assert!(png.icc_profile().unwrap().is_none());
+}
+
+#[test]
+fn from_bytes_and_reader() {
+    let data = noise(10 * 10 * 3);
+    let bytes = encode_png(10, 10, png::ColorType::Rgb, png::BitDepth::Eight, &data);
+
+    assert_eq!(PNG::from_bytes(&bytes).unwrap().get_image_data().unwrap(), data);
+    assert_eq!(PNG::from_reader(std::io::Cursor::new(&bytes)).unwrap().get_image_data().unwrap(), data);
+    assert_eq!(PNG::try_from(bytes.as_slice()).unwrap().get_image_data().unwrap(), data);
+
+    assert!(matches!(PNG::from_bytes(&bytes[1..]), Err(PngError::BadSignature(_))));
+    assert!(matches!(PNG::from_reader(&bytes[..4]), Err(PngError::BadSignature(_))));
+
+    // Both stop at IEND, trailing chunks and garbage are ignored
+    let mut bytes = build_png(&[
+        (b"IHDR", ihdr_data(1, 1, 8, 0, 0)),
+        (b"IDAT", zlib_compress(&[0, 0])),
+        (b"IEND", vec![]),
+        (b"tEXt", b"After\0IEND".to_vec()),
+    ]);
+    bytes.extend(b"trailing garbage");
+    let from_bytes = PNG::from_bytes(&bytes).unwrap();
+    let from_reader = PNG::from_reader(bytes.as_slice()).unwrap();
+    let chunk_types = |png: &PNG| png.chunks.iter().map(|c| c.chunk_type.clone()).collect::<Vec<_>>();
+    assert_eq!(chunk_types(&from_bytes), ["IHDR", "IDAT", "IEND"]);
+    assert_eq!(chunk_types(&from_reader), chunk_types(&from_bytes));
+}
+
+#[test]
+fn chunk_reader() {
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(4, 4, 8, 0, 0)),
+        (b"tEXt", b"Title\0Chunk reader".to_vec()),
+        (b"IDAT", zlib_compress(&[0; 20])),
+        (b"IEND", vec![]),
+        (b"junk", vec![]),
+    ]);
+
+    let mut chunk_reader = ChunkReader::new(bytes.as_slice()).unwrap();
+    chunk_reader.set_skip_data(|chunk_type| chunk_type == "IDAT");
+    let chunks = chunk_reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+    // Stops after IEND
+    let chunk_types = chunks.iter().map(|c| c.chunk_type.as_str()).collect::<Vec<_>>();
+    assert_eq!(chunk_types, ["IHDR", "tEXt", "IDAT", "IEND"]);
+    assert_eq!(chunks[1].data, b"Title\0Chunk reader");
+    assert!(chunks[2].data.is_empty());
+    assert!(chunks[2].length() > 0);
+
+    // Truncated in the middle of IDAT
+    let chunks = ChunkReader::new(&bytes[..bytes.len() - 30]).unwrap().collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 3);
+    assert!(matches!(chunks[2], Err(PngError::Truncated)));
+}
+
+#[test]
+fn png_ref() {
+    let data = noise(10 * 10 * 4);
+    let bytes = encode_png(10, 10, png::ColorType::Rgba, png::BitDepth::Eight, &data);
+
+    let png_ref = PngRef::from_bytes(&bytes).unwrap();
+    let idat = png_ref.find_chunk("IDAT").unwrap();
+    assert!(bytes.as_ptr_range().contains(&idat.data.as_ptr()));
+    assert!(idat.crc_ok());
+
+    assert_eq!(IHDR::try_from_chunk_ref(&png_ref.chunks[0]).unwrap().width, 10);
+    assert_eq!(png_ref.get_image_data().unwrap(), data);
+
+    let png = png_ref.to_owned();
+    assert_eq!(png.chunks.len(), png_ref.chunks.len());
+    assert_eq!(png.get_image_data().unwrap(), data);
+}
+#[test]
+fn open_mmap() {
+    let data = noise(16 * 12 * 3);
+    let bytes = encode_png(16, 12, png::ColorType::Rgb, png::BitDepth::Eight, &data);
+    let path = write_temp("png_open_mmap.png", &bytes);
+
+    // SAFETY: the temporary files are only written before they are mapped
+    let mapped = unsafe { PNG::open_mmap(&path) }.unwrap();
+    assert_eq!(mapped.as_bytes(), bytes.as_slice());
+
+    let png_ref = mapped.as_png_ref();
+    let chunk_types = |png_ref: &PngRef| png_ref.chunks.iter().map(|c| c.chunk_type.to_string()).collect::<Vec<_>>();
+    assert_eq!(chunk_types(&png_ref), chunk_types(&PngRef::from_bytes(&bytes).unwrap()));
+    assert!(png_ref.chunks.iter().all(|c| c.crc_ok()));
+    assert_eq!(png_ref.ihdr().unwrap().width, 16);
+    assert!(mapped.as_bytes().as_ptr_range().contains(&png_ref.find_chunk("IDAT").unwrap().data.as_ptr()));
+    assert_eq!(png_ref.get_image_data().unwrap(), data);
+
+    let path = write_temp("png_open_mmap_bad.png", &bytes[..bytes.len() - 20]);
+    assert!(unsafe { PNG::open_mmap(&path) }.is_err());
+    assert!(matches!(unsafe { PNG::open_mmap("does/not/exist.png") }, Err(PngError::Io(_))));
+}
+
+#[test]
+fn rows() {
+    let data = noise(13 * 9 * 3);
+    let bytes = encode_png_filtered(13, 9, png::ColorType::Rgb, png::BitDepth::Eight, png::FilterType::Paeth, &data);
+    let png = PNG::from_bytes(&bytes).unwrap();
+    let rows = png.rows().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 9);
+    assert_eq!(rows.concat(), data);
+
+    // Rows are streamed across IDAT boundaries, including empty chunks
+    let samples = noise(20 * 6).iter().map(|x| x % 4).collect::<Vec<_>>();
+    let mut scanlines = vec![];
+    for row in pack_samples(&samples, 20, 2).chunks(5) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+    let compressed = zlib_compress(&scanlines);
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(20, 6, 2, 0, 0)),
+        (b"IDAT", compressed[..3].to_vec()),
+        (b"IDAT", vec![]),
+        (b"IDAT", compressed[3..10].to_vec()),
+        (b"IDAT", compressed[10..].to_vec()),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::from_bytes(&bytes).unwrap();
+    assert_eq!(png.rows().unwrap().collect::<Result<Vec<_>, _>>().unwrap().concat(), samples);
+
+    // Missing rows are reported after the complete ones
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(20, 6, 2, 0, 0)),
+        (b"IDAT", zlib_compress(&scanlines[..6 * 3])),
+        (b"IEND", vec![]),
+    ]);
+    let rows = PNG::from_bytes(&bytes).unwrap().rows().unwrap().collect::<Vec<_>>();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[..3].iter().all(|row| row.is_ok()));
+    assert!(matches!(rows[3], Err(PngError::Truncated)));
+
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(20, 6, 2, 0, 1)),
+        (b"IDAT", zlib_compress(&[])),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().rows(), Err(PngError::UnsupportedFormat(_))));
+}
+
+/// Feeds `bytes` to a new streaming decoder in fragments of `fragment_size`, collecting all events.
+fn feed_fragments(bytes: &[u8], fragment_size: usize) -> Result<Vec<Event>, PngError> {
+    let mut decoder = StreamingDecoder::new();
+    let mut events = vec![];
+    for fragment in bytes.chunks(fragment_size) {
+        events.extend(decoder.feed(fragment)?);
+    }
+
+    Ok(events)
+}
+
+fn event_rows(events: &[Event]) -> Vec<u8> {
+    events.iter()
+        .filter_map(|e| match e {
+            Event::Row { data, .. } => { Some(data.as_slice()) }
+            _ => { None }
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+#[test]
+fn streaming_decoder() {
+    let data = noise(31 * 17 * 4);
+    let bytes = encode_png_filtered(31, 17, png::ColorType::Rgba, png::BitDepth::Eight, png::FilterType::Avg, &data);
+
+    for fragment_size in [1, 7, 100, bytes.len()] {
+        let events = feed_fragments(&bytes, fragment_size).unwrap();
+        assert_eq!(event_rows(&events), data);
+
+        assert!(matches!(events[0], Event::Signature));
+        assert!(matches!(&events[1], Event::ChunkStarted { chunk_type, length: 13 } if chunk_type == "IHDR"));
+        assert!(matches!(&events[2], Event::Header(ihdr) if ihdr.width == 31 && ihdr.height == 17));
+        assert!(matches!(&events[3], Event::ChunkCompleted { chunk_type, length: 13, crc_ok: true } if chunk_type == "IHDR"));
+        assert!(matches!(events.last().unwrap(), Event::ImageFinished));
+
+        let indices = events.iter().filter_map(|e| match e { Event::Row { index, .. } => { Some(*index) } _ => { None } });
+        assert!(indices.eq(0..17));
+    }
+
+    // Rows are emitted while the data of a single IDAT chunk is still arriving
+    let mut scanlines = vec![];
+    for row in data.chunks(31 * 4) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+    let compressed = zlib_compress(&scanlines);
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(31, 17, 8, 6, 0)),
+        (b"IDAT", compressed.clone()),
+        (b"IEND", vec![]),
+    ]);
+    let idat_middle = 8 + 25 + 8 + compressed.len() / 2;
+    let mut decoder = StreamingDecoder::new();
+    let first_events = decoder.feed(&bytes[..idat_middle]).unwrap();
+    let first_rows = event_rows(&first_events);
+    assert!(!first_rows.is_empty() && first_rows.len() < data.len());
+    assert!(!first_events.iter().any(|e| matches!(e, Event::ChunkCompleted { chunk_type, .. } if chunk_type == "IDAT")));
+    assert!(!decoder.is_finished());
+    let last_events = decoder.feed(&bytes[idat_middle..]).unwrap();
+    assert!(decoder.is_finished());
+    assert_eq!([first_rows, event_rows(&last_events)].concat(), data);
+    assert!(last_events.iter().any(|e| matches!(e, Event::ChunkCompleted { chunk_type, length, crc_ok: true } if chunk_type == "IDAT" && *length as usize == compressed.len())));
+
+    // Interlaced rows are emitted once all passes have arrived
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(13, 11, 8, 2, 1)),
+        (b"IDAT", zlib_compress(&adam7_scanlines(&data[..13 * 11 * 3], 13, 11, 3))),
+        (b"IEND", vec![]),
+    ]);
+    assert_eq!(event_rows(&feed_fragments(&bytes, 5).unwrap()), &data[..13 * 11 * 3]);
+
+    // Errors
+    assert!(matches!(feed_fragments(&[1, 2, 3, 4, 5, 6, 7, 8], 3), Err(PngError::BadSignature(_))));
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(4, 4, 8, 0, 0)),
+        (b"IDAT", zlib_compress(&[0; 10])),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(feed_fragments(&bytes, 4), Err(PngError::Truncated)));
+    let bytes = build_png(&[(b"IDAT", zlib_compress(&[0; 20])), (b"IEND", vec![])]);
+    assert!(matches!(feed_fragments(&bytes, 4), Err(PngError::MissingChunk(_))));
+
+    // CRC mismatches are checked across fragments
+    let mut bytes = encode_png(4, 4, png::ColorType::Grayscale, png::BitDepth::Eight, &[0; 16]);
+    let idat_crc = bytes.len() - 12 - 1;
+    bytes[idat_crc] ^= 1;
+    assert!(matches!(feed_fragments(&bytes, 3), Err(PngError::BadCrc { chunk_type, .. }) if chunk_type == "IDAT"));
+    let mut decoder = StreamingDecoder::new();
+    decoder.set_crc_policy(CrcPolicy::Warn);
+    let events = decoder.feed(&bytes).unwrap();
+    assert!(events.iter().any(|e| matches!(e, Event::ChunkCompleted { chunk_type, crc_ok: false, .. } if chunk_type == "IDAT")));
+    assert!(decoder.is_finished());
+}
+
+#[test]
+fn decode_lenient() {
+    let (width, height) = (40, 30);
+    let data = noise(width * height * 3);
+    let bytes = encode_png_filtered(width as u32, height as u32, png::ColorType::Rgb, png::BitDepth::Eight, png::FilterType::Sub, &data);
+    let fill = [255, 0, 255];
+
+    // Intact files are decoded completely
+    let (png, errors) = PNG::from_bytes_lenient(&bytes).unwrap();
+    assert!(errors.is_empty());
+    let (image, report) = png.decode_lenient(&fill).unwrap();
+    assert!(report.is_complete());
+    assert_eq!(report.rows_recovered, height as u32);
+    assert_eq!(image.data, data);
+
+    // Cut off in the middle of IDAT
+    let (png, errors) = PNG::from_bytes_lenient(&bytes[..bytes.len() / 2]).unwrap();
+    assert!(matches!(errors[..], [PngError::Truncated]));
+    let (image, report) = png.decode_lenient(&fill).unwrap();
+    assert!(matches!(report.errors[..], [PngError::Truncated]));
+
+    let recovered = report.rows_recovered as usize;
+    assert!(recovered > 0 && recovered < height);
+    let row_length = width * 3;
+    assert_eq!(image.data.len(), data.len());
+    assert_eq!(image.data[..recovered * row_length], data[..recovered * row_length]);
+    assert!(image.data[recovered * row_length..].chunks(3).all(|pixel| pixel == fill));
+
+    // Corrupt CRCs and invalid filter types are reported
+    let mut scanlines = vec![];
+    for (y, row) in data.chunks(row_length).enumerate() {
+        scanlines.push(if y == 5 { 9 } else { 0 });
+        scanlines.extend(row);
+    }
+    let mut bytes = build_png(&[
+        (b"IHDR", ihdr_data(width as u32, height as u32, 8, 2, 0)),
+        (b"IDAT", zlib_compress(&scanlines)),
+        (b"IEND", vec![]),
+    ]);
+    let crc_offset = bytes.len() - 16;
+    bytes[crc_offset] ^= 1;
+    let (png, errors) = PNG::from_bytes_lenient(&bytes).unwrap();
+    assert!(matches!(&errors[..], [PngError::BadCrc { chunk_type, .. }] if chunk_type == "IDAT"));
+    let (image, report) = png.decode_lenient(&fill).unwrap();
+    assert_eq!(report.rows_recovered, 5);
+    assert!(matches!(report.errors[..], [PngError::InvalidChunk { .. }]));
+    assert_eq!(image.data[..5 * row_length], data[..5 * row_length]);
+
+    assert!(matches!(png.decode_lenient(&[0, 0]), Err(PngError::InvalidArgument(_))));
+    assert!(matches!(PNG::from_bytes_lenient(&bytes[1..]), Err(PngError::BadSignature(_))));
+
+    // A bit flipped in the IHDR width can't be recovered from, the image size would be wrong
+    bytes[8 + 8 + 1] ^= 0x10;
+    let (png, errors) = PNG::from_bytes_lenient(&bytes).unwrap();
+    assert!(matches!(&errors[..], [PngError::BadCrc { chunk_type, .. }, _] if chunk_type == "IHDR"));
+    assert!(matches!(png.decode_lenient(&fill), Err(PngError::BadCrc { .. })));
+}
+
+/// Scanlines without filtering, for building chunks by hand.
+fn unfiltered_scanlines(data: &[u8], row_length: usize) -> Vec<u8> {
+    let mut scanlines = vec![];
+    for row in data.chunks(row_length) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+
+    scanlines
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fctl_data(sequence_number: u32, width: u32, height: u32, x: u32, y: u32, delay: (u16, u16), dispose_op: u8, blend_op: u8) -> Vec<u8> {
+    let mut data = vec![];
+    for value in [sequence_number, width, height, x, y] {
+        data.extend(value.to_be_bytes());
+    }
+    data.extend(delay.0.to_be_bytes());
+    data.extend(delay.1.to_be_bytes());
+    data.extend([dispose_op, blend_op]);
+
+    data
+}
+
+fn fdat_data(sequence_number: u32, pixels: &[u8], row_length: usize) -> Vec<u8> {
+    [sequence_number.to_be_bytes().to_vec(), zlib_compress(&unfiltered_scanlines(pixels, row_length))].concat()
+}
+
+#[test]
+fn apng_frames() {
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+        (b"acTL", [4_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        // Red background, kept
+        (b"fcTL", fctl_data(0, 3, 2, 0, 0, (1, 10), 0, 0)),
+        (b"IDAT", zlib_compress(&unfiltered_scanlines(&RED.repeat(6), 12))),
+        // Half transparent blue over red, then cleared
+        (b"fcTL", fctl_data(1, 1, 1, 1, 0, (5, 0), 1, 1)),
+        (b"fdAT", fdat_data(2, &[0, 0, 255, 128], 4)),
+        // Green and transparent replacing red, then restored
+        (b"fcTL", fctl_data(3, 2, 1, 1, 1, (1, 1), 2, 0)),
+        (b"fdAT", fdat_data(4, &[GREEN, CLEAR].concat(), 8)),
+        // Transparent over the canvas changes nothing
+        (b"fcTL", fctl_data(5, 1, 1, 0, 0, (1, 1), 0, 1)),
+        (b"fdAT", fdat_data(6, &CLEAR, 4)),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::from_bytes(&bytes).unwrap();
+
+    let actl = acTL::try_from_chunk(png.find_chunk("acTL").unwrap()).unwrap();
+    assert_eq!((actl.num_frames(), actl.num_plays()), (4, 0));
+    let fctl = fcTL::try_from_chunk(png.chunks.iter().filter(|c| c.chunk_type == "fcTL").nth(2).unwrap()).unwrap();
+    assert_eq!((fctl.width(), fctl.height(), fctl.x_offset(), fctl.y_offset()), (2, 1, 1, 1));
+    assert_eq!((fctl.dispose_op(), fctl.blend_op()), (DisposeOp::Previous, BlendOp::Source));
+
+    let frames = png.frames().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(frames.len(), 4);
+    assert!(frames.iter().all(|f| (f.image.width, f.image.height, f.image.color_type) == (3, 2, ColorType::TrueColorAlpha)));
+    assert_eq!(frames[0].delay, std::time::Duration::from_millis(100));
+    assert_eq!(frames[1].delay, std::time::Duration::from_millis(50));
+
+    assert_eq!(frames[0].image.data, RED.repeat(6));
+    assert_eq!(frames[1].image.data, [RED, [127, 0, 128, 255], RED, RED, RED, RED].concat());
+    assert_eq!(frames[2].image.data, [RED, CLEAR, RED, RED, GREEN, CLEAR].concat());
+    assert_eq!(frames[3].image.data, [RED, CLEAR, RED, RED, RED, RED].concat());
+
+    // Without acTL the default image is the only frame
+    let data = noise(5 * 4 * 3);
+    let bytes = encode_png(5, 4, png::ColorType::Rgb, png::BitDepth::Eight, &data);
+    let png = PNG::from_bytes(&bytes).unwrap();
+    let frames = png.frames().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].image.data, png.decode_as(PixelFormat::Rgba8).unwrap().data);
+
+    // Frames must fit on the canvas
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+        (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        (b"IDAT", zlib_compress(&unfiltered_scanlines(&RED.repeat(6), 12))),
+        (b"fcTL", fctl_data(0, 2, 2, 2, 0, (1, 10), 0, 0)),
+        (b"fdAT", fdat_data(1, &RED.repeat(4), 8)),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().frames(), Err(PngError::InvalidChunk { .. })));
+
+    // The frame of the default image must cover the whole canvas
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+        (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        (b"fcTL", fctl_data(0, 2, 2, 1, 0, (1, 10), 0, 0)),
+        (b"IDAT", zlib_compress(&unfiltered_scanlines(&RED.repeat(4), 8))),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().frames(), Err(PngError::InvalidChunk { .. })));
+
+    // fdAT can't add to the frame of the default image
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+        (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        (b"fcTL", fctl_data(0, 3, 2, 0, 0, (1, 10), 0, 0)),
+        (b"IDAT", zlib_compress(&unfiltered_scanlines(&RED.repeat(6), 12))),
+        (b"fdAT", fdat_data(1, &RED, 4)),
+        (b"IEND", vec![]),
+    ]);
+    assert!(matches!(PNG::from_bytes(&bytes).unwrap().frames(), Err(PngError::InvalidChunk { .. })));
+
+    // The canvas isn't allocated from the IHDR dimensions before a frame has decoded
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(60000, 60000, 8, 6, 0)),
+        (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        (b"fcTL", fctl_data(0, 60000, 60000, 0, 0, (1, 10), 0, 0)),
+        (b"IDAT", zlib_compress(&[0; 64])),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::from_bytes(&bytes).unwrap();
+    let mut frames = png.frames().unwrap();
+    assert!(matches!(frames.next(), Some(Err(PngError::Truncated))));
+
+    // Sequence numbers must increase by one without gaps
+    for (fctl_sequence, fdat_sequence) in [(0, 2), (1, 2), (0, 0)] {
+        let bytes = build_png(&[
+            (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+            (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+            (b"IDAT", zlib_compress(&unfiltered_scanlines(&RED.repeat(6), 12))),
+            (b"fcTL", fctl_data(fctl_sequence, 1, 1, 0, 0, (1, 10), 0, 0)),
+            (b"fdAT", fdat_data(fdat_sequence, &GREEN, 4)),
+            (b"IEND", vec![]),
+        ]);
+        assert!(matches!(PNG::from_bytes(&bytes).unwrap().frames(), Err(PngError::InvalidChunk { .. })));
+    }
+
+    // A default image frame without image data is missing IDAT, not fdAT
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 6, 0)),
+        (b"acTL", [1_u32.to_be_bytes(), 0_u32.to_be_bytes()].concat()),
+        (b"fcTL", fctl_data(0, 3, 2, 0, 0, (1, 10), 0, 0)),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::from_bytes(&bytes).unwrap();
+    let mut frames = png.frames().unwrap();
+    assert!(matches!(frames.next(), Some(Err(PngError::MissingChunk(chunk_type))) if chunk_type == "IDAT"));
+}
+
+#[test]
+fn parsed_chunks() {
+    let chunks = |color_type: u8, background: Vec<u8>, transparency: Vec<u8>| build_png(&[
+        (b"IHDR", ihdr_data(1, 1, 8, color_type, 0)),
+        (b"bKGD", background),
+        (b"tRNS", transparency),
+        (b"tEXt", b"Title\0Parsed".to_vec()),
+        (b"prVt", vec![1, 2, 3]),
+        (b"IDAT", zlib_compress(&[0, 0, 0, 0])),
+        (b"IEND", vec![]),
+    ]);
+
+    let png = PNG::from_bytes(&chunks(0, vec![0, 1], vec![0, 2])).unwrap();
+    let parsed = png.parsed_chunks().unwrap();
+    assert_eq!(parsed.len(), 7);
+    assert!(matches!(&parsed[0], ParsedChunk::IHDR(ihdr) if ihdr.color_type == ColorType::Greyscale));
+    assert!(matches!(parsed[1], ParsedChunk::bKGD_Greyscale(_)));
+    assert!(matches!(parsed[2], ParsedChunk::tRNS_Greyscale(_)));
+    assert!(matches!(parsed[3], ParsedChunk::tEXt(_)));
+    assert!(matches!(&parsed[4], ParsedChunk::Unknown(chunk) if chunk.chunk_type == "prVt" && chunk.data == [1, 2, 3]));
+    assert!(matches!(&parsed[5], ParsedChunk::IDAT(data) if *data == zlib_compress(&[0, 0, 0, 0])));
+    assert!(matches!(parsed[6], ParsedChunk::IEND));
+
+    let png = PNG::from_bytes(&chunks(2, vec![0; 6], vec![0; 6])).unwrap();
+    let parsed = png.parsed_chunks().unwrap();
+    assert!(matches!(parsed[1], ParsedChunk::bKGD_TrueColor(_)));
+    assert!(matches!(parsed[2], ParsedChunk::tRNS_TrueColor(_)));
+
+    let png = PNG::from_bytes(&chunks(3, vec![0], vec![0, 0])).unwrap();
+    let parsed = png.parsed_chunks().unwrap();
+    assert!(matches!(parsed[1], ParsedChunk::bKGD_Indexed(_)));
+    assert!(matches!(parsed[2], ParsedChunk::tRNS_Indexed(_)));
+
+    // tRNS is not allowed with an alpha channel
+    let png = PNG::from_bytes(&chunks(6, vec![0; 6], vec![0; 6])).unwrap();
+    assert!(matches!(png.parsed_chunks(), Err(PngError::InvalidChunk { chunk_type, .. }) if chunk_type == "tRNS"));
+
+    // bKGD cannot be interpreted without IHDR
+    let png = PNG::from_bytes(&build_png(&[(b"bKGD", vec![0, 1]), (b"IEND", vec![])])).unwrap();
+    assert!(matches!(png.parsed_chunks(), Err(PngError::MissingChunk(chunk_type)) if chunk_type == "IHDR"));
+}
+
+#[test]
+fn metadata() {
+    let mut profile = vec![0_u8; 128];
+    profile[..4].copy_from_slice(&128_u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+
+    let chrm = [31270_u32, 32900, 64000, 33000, 30000, 60000, 15000, 6000];
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(2, 1, 16, 2, 1)),
+        (b"gAMA", 45455_u32.to_be_bytes().to_vec()),
+        (b"cHRM", chrm.iter().flat_map(|v| v.to_be_bytes()).collect()),
+        (b"sRGB", vec![1]),
+        (b"iCCP", [b"Profile\0\0".to_vec(), zlib_compress(&profile)].concat()),
+        (b"pHYs", [2835_u32.to_be_bytes(), 2835_u32.to_be_bytes()].concat().into_iter().chain([1]).collect()),
+        (b"tIME", vec![7, 232, 2, 29, 13, 45, 59]),
+        (b"bKGD", vec![0, 1, 0, 2, 0, 3]),
+        (b"tRNS", vec![1, 0, 2, 0, 3, 0]),
+        (b"tEXt", b"Title\0First".to_vec()),
+        (b"tEXt", b"Title\0Second".to_vec()),
+        (b"zTXt", [b"Comment\0\0".to_vec(), zlib_compress(b"Compressed")].concat()),
+        (b"iTXt", b"Author\0\0\0en\0Autor\0International".to_vec()),
+        (b"IDAT", zlib_compress(&adam7_scanlines(&[0; 12], 2, 1, 6))),
+        (b"IEND", vec![]),
+    ]);
+    let metadata = PNG::from_bytes(&bytes).unwrap().metadata().unwrap();
+
+    assert_eq!((metadata.width, metadata.height, metadata.bit_depth), (2, 1, 16));
+    assert_eq!(metadata.color_type, ColorType::TrueColor);
+    assert!(metadata.interlaced);
+    assert!((metadata.gamma.unwrap() - 0.45455).abs() < 1e-6);
+    assert_eq!(metadata.chromaticities, Some(Chromaticities {
+        white: (0.3127, 0.329),
+        red: (0.64, 0.33),
+        green: (0.3, 0.6),
+        blue: (0.15, 0.06),
+    }));
+    assert_eq!(metadata.srgb_intent, Some(RenderingIntent::RelativeColorimetric));
+    assert_eq!(metadata.icc_profile.unwrap().profile(), profile);
+
+    let physical_size = metadata.physical_size.unwrap();
+    assert_eq!((physical_size.pixels_per_unit_x, physical_size.pixels_per_unit_y), (2835, 2835));
+    assert_eq!(physical_size.unit, PixelUnit::Meter);
+    assert_eq!(metadata.modification_time, Some(ModificationTime { year: 2024, month: 2, day: 29, hour: 13, minute: 45, second: 59 }));
+    assert_eq!(metadata.background, Some(Background::TrueColor(1, 2, 3)));
+    assert_eq!(metadata.transparency, Some(Transparency::TrueColor(256, 512, 768)));
+
+    let text = metadata.text.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>();
+    assert_eq!(text, [("Author", "International"), ("Comment", "Compressed"), ("Title", "First")]);
+
+    // Images without ancillary chunks
+    let bytes = encode_indexed(1, 1, png::BitDepth::Eight, &[1, 2, 3], Some(&[7]), &[0]);
+    let metadata = PNG::from_bytes(&bytes).unwrap().metadata().unwrap();
+    assert_eq!(metadata.color_type, ColorType::IndexedColor);
+    assert!(!metadata.interlaced);
+    assert_eq!(metadata.transparency, Some(Transparency::Indexed(vec![7])));
+    assert!(metadata.gamma.is_none() && metadata.background.is_none() && metadata.text.is_empty());
+    assert!(metadata.errors.is_empty());
+
+    // Malformed ancillary chunks are skipped and reported, everything else is still there
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(1, 1, 8, 0, 0)),
+        (b"tIME", vec![7, 232, 13]),
+        (b"iCCP", b"Profile\0\0not zlib".to_vec()),
+        (b"tEXt", b"Title\0Still here".to_vec()),
+        (b"IDAT", zlib_compress(&[0, 0])),
+        // Not part of the metadata, so not parsed at all
+        (b"fdAT", vec![0]),
+        (b"IEND", vec![]),
+    ]);
+    let metadata = PNG::from_bytes(&bytes).unwrap().metadata().unwrap();
+    assert_eq!((metadata.width, metadata.height), (1, 1));
+    assert!(metadata.modification_time.is_none() && metadata.icc_profile.is_none());
+    assert_eq!(metadata.text.get("Title").map(String::as_str), Some("Still here"));
+    assert_eq!(metadata.errors.len(), 2);
+    assert!(matches!(metadata.errors[0], PngError::InvalidChunk { .. }));
+    assert!(matches!(metadata.errors[1], PngError::Decompress(_)));
+}
+
+#[test]
+fn chunk_accessors() {
+    let chunk = |chunk_type: &[u8; 4], data: &[u8]| PNG::from_bytes(&build_png(&[(chunk_type, data.to_vec())])).unwrap().chunks.remove(0);
+
+    let ihdr = IHDR::try_from_chunk(&chunk(b"IHDR", &ihdr_data(3, 2, 4, 3, 1))).unwrap();
+    assert_eq!((ihdr.width, ihdr.height, ihdr.bit_depth(), ihdr.color_type), (3, 2, 4, ColorType::IndexedColor));
+    assert_eq!((ihdr.compression_method(), ihdr.filter_method(), ihdr.interlace_method()), (0, 0, InterlaceMethod::Adam7));
+
+    let gama = gAMA::try_from_chunk(&chunk(b"gAMA", &45455_u32.to_be_bytes())).unwrap();
+    assert_eq!(gama.gamma_raw(), 45455);
+    assert_eq!(gama.gamma(), 0.45455);
+
+    assert_eq!(sRGB::try_from_chunk(&chunk(b"sRGB", &[3])).unwrap().intent(), RenderingIntent::AbsoluteColorimetric);
+
+    let phys = pHYs::try_from_chunk(&chunk(b"pHYs", &[0, 0, 0, 2, 0, 0, 0, 1, 0])).unwrap();
+    assert_eq!((phys.pixels_per_unit_x(), phys.pixels_per_unit_y(), phys.unit()), (2, 1, PixelUnit::Unknown));
+
+    let chrm_data = [31270_u32, 32900, 64000, 33000, 30000, 60000, 15000, 6000].iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+    let chrm = cHRM::try_from_chunk(&chunk(b"cHRM", &chrm_data)).unwrap();
+    assert_eq!((chrm.white_point(), chrm.red(), chrm.green(), chrm.blue()), ((0.3127, 0.329), (0.64, 0.33), (0.3, 0.6), (0.15, 0.06)));
+
+    let plte = PLTE::try_from_chunk(&chunk(b"PLTE", &[1, 2, 3, 4, 5, 6])).unwrap();
+    assert_eq!(plte.entries(), [[1, 2, 3], [4, 5, 6]]);
+    assert_eq!(tRNS_Indexed::try_from_chunk(&chunk(b"tRNS", &[0, 128])).unwrap().values(), [0, 128]);
+    assert_eq!(bKGD_Greyscale::try_from_chunk(&chunk(b"bKGD", &[1, 2])).unwrap().value(), 258);
+
+    let time = tIME::try_from_chunk(&chunk(b"tIME", &[7, 232, 12, 31, 23, 59, 60])).unwrap();
+    assert_eq!((time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second()), (2024, 12, 31, 23, 59, 60));
+
+    let text = tEXt::try_from_chunk(&chunk(b"tEXt", b"Title\0Accessors")).unwrap();
+    assert_eq!((text.keyword(), text.text()), ("Title", "Accessors"));
+    let ztxt = zTXt::try_from_chunk(&chunk(b"zTXt", &[b"Comment\0\0".to_vec(), zlib_compress(b"Inflated")].concat())).unwrap();
+    assert_eq!((ztxt.keyword(), ztxt.text(), ztxt.compression_method()), ("Comment", "Inflated", CompressionMethod::ZLIB));
+    let itxt = iTXt::try_from_chunk(&chunk(b"iTXt", b"Author\0\0\0en\0Autor\0Text")).unwrap();
+    assert_eq!((itxt.keyword(), itxt.language_tag(), itxt.translated_keyword(), itxt.text()), ("Author", Some("en"), Some("Autor"), "Text"));
+    assert!(!itxt.is_compressed());
+}
+
+/// Assembles the TIFF structure of an eXIf chunk, with the Exif and GPS IFDs and a thumbnail IFD.
+fn exif_data(little_endian: bool) -> Vec<u8> {
+    let u16b = |v: u16| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32b = |v: u32| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+    let rational = |n: u32, d: u32| [u32b(n), u32b(d)].concat();
+
+    // IFD offset, entries as (tag, type, count, value) and the offset of the next IFD
+    let ifds = [
+        (8, vec![
+            (0x010F, 2, 7, b"Pngcam\0".to_vec()),
+            (0x0112, 3, 1, u16b(6).to_vec()),
+            (0x011A, 5, 1, rational(72, 1)),
+            (0x8769, 4, 1, u32b(92).to_vec()),
+            (0x8825, 4, 1, u32b(122).to_vec()),
+        ], 74),
+        (74, vec![(0x0103, 3, 1, u16b(6).to_vec())], 0),
+        (92, vec![
+            (0x9003, 2, 20, b"2024:01:02 03:04:05\0".to_vec()),
+            (0x9204, 10, 1, rational(-1_i32 as u32, 3)),
+        ], 0),
+        (122, vec![
+            (0x0001, 2, 2, b"N\0".to_vec()),
+            (0x0002, 5, 3, [rational(51, 1), rational(30, 1), rational(2634, 100)].concat()),
+        ], 0),
+    ];
+
+    let values_offset = 152;
+    let mut data = if little_endian { b"II\x2a\0".to_vec() } else { b"MM\0\x2a".to_vec() };
+    data.extend(u32b(8));
+    let mut values = vec![];
+    for (offset, entries, next) in ifds {
+        assert_eq!(data.len(), offset as usize);
+        data.extend(u16b(entries.len() as u16));
+        for (tag, field_type, count, mut value) in entries {
+            data.extend(u16b(tag));
+            data.extend(u16b(field_type));
+            data.extend(u32b(count));
+            if value.len() > 4 {
+                data.extend(u32b(values_offset + values.len() as u32));
+                values.extend(value);
+            } else {
+                value.resize(4, 0);
+                data.extend(value);
+            }
+        }
+        data.extend(u32b(next));
+    }
+    assert_eq!(data.len(), values_offset as usize);
+    data.extend(values);
+
+    data
+}
+
+#[test]
+fn apply_orientation() {
+    let options = DecodeOptions { apply_orientation: true, ..Default::default() };
+    // Orientation only, big-endian
+    let exif = |orientation: u16| [b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec(), orientation.to_be_bytes().to_vec(), vec![0; 6]].concat();
+
+    // 3x2 greyscale, pixels numbered 1 to 6 in reading order
+    let bytes = |exif: Vec<u8>| build_png(&[
+        (b"IHDR", ihdr_data(3, 2, 8, 0, 0)),
+        (b"eXIf", exif),
+        (b"IDAT", zlib_compress(&[0, 1, 2, 3, 0, 4, 5, 6])),
+        (b"IEND", vec![]),
+    ]);
+
+    let expected: [(u16, (u32, u32), [u8; 6]); 8] = [
+        (1, (3, 2), [1, 2, 3, 4, 5, 6]),
+        (2, (3, 2), [3, 2, 1, 6, 5, 4]),
+        (3, (3, 2), [6, 5, 4, 3, 2, 1]),
+        (4, (3, 2), [4, 5, 6, 1, 2, 3]),
+        (5, (2, 3), [1, 4, 2, 5, 3, 6]),
+        (6, (2, 3), [4, 1, 5, 2, 6, 3]),
+        (7, (2, 3), [6, 3, 5, 2, 4, 1]),
+        (8, (2, 3), [3, 6, 2, 5, 1, 4]),
+    ];
+    for (orientation, (width, height), data) in expected {
+        let png = PNG::from_bytes(&bytes(exif(orientation))).unwrap();
+        let image = png.decode_with(&options).unwrap();
+        assert_eq!((image.width, image.height, image.data), (width, height, data.to_vec()), "orientation {}", orientation);
+
+        // Opt-in only
+        assert_eq!(png.decode_with(&DecodeOptions::default()).unwrap().data, [1, 2, 3, 4, 5, 6]);
+    }
+
+    // eXIf without the tag
+    let png = PNG::from_bytes(&bytes(b"MM\0\x2a\0\0\0\x08\0\0\0\0\0\0".to_vec())).unwrap();
+    assert_eq!(png.decode_with(&options).unwrap().data, [1, 2, 3, 4, 5, 6]);
+
+    // eXIf that can't be parsed
+    let png = PNG::from_bytes(&bytes(b"MM\0\x2b\0\0\0\x08\0\0".to_vec())).unwrap();
+    assert_eq!(png.decode_with(&options).unwrap().data, [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn itxt() {
+    let itxt = |data: Vec<u8>| iTXt::try_from_chunk(&PNG::from_bytes(&build_png(&[(b"iTXt", data)])).unwrap().chunks[0]);
+
+    // Compressed, with multi-byte UTF-8
+    let text = "Grüße aus Köln, 日本語のテキスト";
+    let compressed = itxt([b"Comment\0\x01\0de\0Kommentar\0".to_vec(), zlib_compress(text.as_bytes())].concat()).unwrap();
+    assert!(compressed.is_compressed());
+    assert_eq!((compressed.language_tag(), compressed.translated_keyword(), compressed.text()), (Some("de"), Some("Kommentar"), text));
+
+    // The text runs to the end of the chunk
+    let uncompressed = itxt(b"Comment\0\0\0\0\0First\0Second".to_vec()).unwrap();
+    assert_eq!((uncompressed.language_tag(), uncompressed.translated_keyword(), uncompressed.text()), (None, None, "First\0Second"));
+
+    // Translated keyword without a language tag
+    let translated = itxt("Title\0\0\0\0Titel\0Text".as_bytes().to_vec()).unwrap();
+    assert_eq!((translated.language_tag(), translated.translated_keyword()), (None, Some("Titel")));
+
+    for invalid in [
+        b"Comment\0\x02\0\0\0Text".to_vec(),
+        b"Comment\0\0\0en".to_vec(),
+        b"Comment\0\0\0e n\0\0Text".to_vec(),
+        b"Comment\0\x01\0\0\0Not zlib".to_vec(),
+    ] {
+        assert!(itxt(invalid).is_err());
+    }
+}
+
+#[test]
+fn exif() {
+    for (little_endian, byte_align) in [(true, ByteAlign::Intel), (false, ByteAlign::Motorola)] {
+        let png = PNG::from_bytes(&build_png(&[(b"eXIf", exif_data(little_endian))])).unwrap();
+        let exif = eXIf::try_from_chunk(&png.chunks[0]).unwrap();
+        assert_eq!(exif.byte_align(), byte_align);
+
+        let kinds = exif.ifds().iter().map(|ifd| ifd.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, [IfdKind::Primary, IfdKind::Thumbnail, IfdKind::Exif, IfdKind::Gps]);
+
+        assert_eq!(exif.get("Make").unwrap().value(), &ExifValue::Ascii(String::from("Pngcam")));
+        let orientation = exif.get("Orientation").unwrap();
+        assert_eq!((orientation.tag(), orientation.field_type(), orientation.count()), (0x0112, 3, 1));
+        assert_eq!(orientation.value().as_u32(), Some(6));
+        assert_eq!(exif.get("XResolution").unwrap().value(), &ExifValue::Rational(vec![(72, 1)]));
+        assert_eq!(exif.ifd(IfdKind::Thumbnail).unwrap().get(0x0103).unwrap().value(), &ExifValue::Short(vec![6]));
+
+        assert_eq!(exif.get("DateTimeOriginal").unwrap().value().as_str(), Some("2024:01:02 03:04:05"));
+        assert_eq!(exif.get("ExposureBiasValue").unwrap().value(), &ExifValue::SRational(vec![(-1, 3)]));
+        assert_eq!(exif.get("GPSLatitudeRef").unwrap().value().as_str(), Some("N"));
+        assert_eq!(exif.get("GPSLatitude").unwrap().value().to_f64s(), Some(vec![51.0, 30.0, 26.34]));
+        assert!(exif.get("GPSLongitude").is_none());
+        assert_eq!(tag_name(IfdKind::Gps, 0x0002), Some("GPSLatitude"));
+    }
+
+    let mut looping = exif_data(true);
+    looping[70..74].copy_from_slice(&8_u32.to_le_bytes());
+    let png = PNG::from_bytes(&build_png(&[(b"eXIf", looping)])).unwrap();
+    assert!(matches!(eXIf::try_from_chunk(&png.chunks[0]), Err(PngError::InvalidChunk { .. })));
+
+    // The Exif IFD pointer leading back to IFD0
+    let mut looping = exif_data(true);
+    looping[54..58].copy_from_slice(&8_u32.to_le_bytes());
+    let png = PNG::from_bytes(&build_png(&[(b"eXIf", looping)])).unwrap();
+    assert!(matches!(eXIf::try_from_chunk(&png.chunks[0]), Err(PngError::InvalidChunk { .. })));
+
+    // Two entries each taking the whole chunk as their value
+    let mut overlapping = exif_data(true);
+    let len = overlapping.len() as u32;
+    for entry in [10, 34] {
+        overlapping[entry + 2..entry + 4].copy_from_slice(&2_u16.to_le_bytes());
+        overlapping[entry + 4..entry + 8].copy_from_slice(&len.to_le_bytes());
+        overlapping[entry + 8..entry + 12].copy_from_slice(&0_u32.to_le_bytes());
+    }
+    let png = PNG::from_bytes(&build_png(&[(b"eXIf", overlapping)])).unwrap();
+    assert!(matches!(eXIf::try_from_chunk(&png.chunks[0]), Err(PngError::InvalidChunk { .. })));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let bytes = build_png(&[
+        (b"IHDR", ihdr_data(1, 1, 8, 2, 0)),
+        (b"gAMA", 45455_u32.to_be_bytes().to_vec()),
+        (b"bKGD", vec![0, 1, 0, 2, 0, 3]),
+        (b"tEXt", b"Title\0Serialized".to_vec()),
+        (b"prVt", vec![1, 2, 3]),
+        (b"IDAT", zlib_compress(&[0, 1, 2, 3])),
+        (b"IEND", vec![]),
+    ]);
+    let png = PNG::from_bytes(&bytes).unwrap();
+
+    // Chunk lists
+    let json = serde_json::to_string(&png).unwrap();
+    let restored: PNG = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.get_image_data().unwrap(), [1, 2, 3]);
+    assert!(restored.chunks.iter().all(|c| c.crc_ok()));
+
+    // Parsed chunks
+    let parsed = png.parsed_chunks().unwrap();
+    let json = serde_json::to_string(&parsed).unwrap();
+    let restored: Vec<ParsedChunk> = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", restored), format!("{:?}", parsed));
+    assert!(matches!(&restored[0], ParsedChunk::IHDR(ihdr) if ihdr.color_type == ColorType::TrueColor && ihdr.bit_depth() == 8));
+
+    // Metadata
+    let metadata = png.metadata().unwrap();
+    let json = serde_json::to_value(&metadata).unwrap();
+    assert_eq!(json["color_type"], "TrueColor");
+    assert_eq!(json["text"]["Title"], "Serialized");
+    let restored: png_reader::metadata::Metadata = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.background, Some(Background::TrueColor(1, 2, 3)));
+    assert_eq!(restored.gamma, metadata.gamma);
+}